@@ -0,0 +1,183 @@
+use crate::kmath::*;
+use crate::dsp::SAMPLE_RATE;
+
+// Arpeggiator. Anything that makes notes feeds note_on / note_off, the keyboard directly
+// and the sequencer as its steps come round (theres no MIDI in yet, it would go the same way).
+// run turns the held notes into timed notes on the audio clock for whoever sends them.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArpMode {
+    Up,
+    Down,
+    UpDown,
+    Random,
+    Played,
+}
+
+impl ArpMode {
+    pub const ALL: [ArpMode; 5] = [ArpMode::Up, ArpMode::Down, ArpMode::UpDown, ArpMode::Random, ArpMode::Played];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ArpMode::Up => "up",
+            ArpMode::Down => "down",
+            ArpMode::UpDown => "up-down",
+            ArpMode::Random => "random",
+            ArpMode::Played => "played",
+        }
+    }
+
+    pub fn from_param(p: f32) -> ArpMode {
+        ArpMode::ALL[(p.round().max(0.0) as usize).min(ArpMode::ALL.len() - 1)]
+    }
+}
+
+// steps per beat, straight and triplet
+pub const ARP_RATES: [f32; 6] = [1.0, 2.0, 3.0, 4.0, 6.0, 8.0];
+
+#[derive(Debug, Clone, Copy)]
+pub struct ArpNote {
+    pub note: i32,
+    pub on: u64,
+    pub off: u64,
+}
+
+pub struct Arp {
+    pub mode: ArpMode,
+    pub octaves: usize,
+    pub rate: f32,      // steps per beat
+    pub gate: f32,      // fraction of a step
+    pub swing: f32,     // how far the off beats get pushed, fraction of a step
+    pub latch: bool,
+    pub bpm: f32,
+
+    played: Vec<i32>,   // in the order they went down
+    down: usize,        // keys physically held, latch keeps played after this hits 0
+    running: bool,
+    next: f64,          // unswung start of the next step, in samples
+    step: u64,
+    pos: usize,
+    seed: u32,
+}
+
+impl Default for Arp {
+    fn default() -> Self {
+        Arp {
+            mode: ArpMode::Up,
+            octaves: 1,
+            rate: 4.0,
+            gate: 0.5,
+            swing: 0.0,
+            latch: false,
+            bpm: 120.0,
+            played: vec![],
+            down: 0,
+            running: false,
+            next: 0.0,
+            step: 0,
+            pos: 0,
+            seed: 0,
+        }
+    }
+}
+
+impl Arp {
+    pub fn note_on(&mut self, note: i32) {
+        // with latch, the first key after letting go of everything starts a new chord
+        if self.latch && self.down == 0 {
+            self.played.clear();
+        }
+        self.down += 1;
+        if !self.played.contains(&note) {
+            self.played.push(note);
+        }
+    }
+
+    pub fn note_off(&mut self, note: i32) {
+        self.down = self.down.saturating_sub(1);
+        if !self.latch {
+            self.played.retain(|n| *n != note);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.played.clear();
+        self.down = 0;
+        self.running = false;
+    }
+
+    // turning latch off lets go of anything that isnt held
+    pub fn set_latch(&mut self, latch: bool) {
+        if self.latch && !latch && self.down == 0 {
+            self.played.clear();
+        }
+        self.latch = latch;
+    }
+
+    pub fn step_len(&self) -> f64 {
+        SAMPLE_RATE as f64 * 60.0 / (self.bpm.max(1.0) as f64 * self.rate.max(0.01) as f64)
+    }
+
+    fn pattern(&self) -> Vec<i32> {
+        let mut notes = self.played.clone();
+        if self.mode != ArpMode::Played {
+            notes.sort();
+        }
+        let mut pattern: Vec<i32> = (0..self.octaves.max(1) as i32)
+            .flat_map(|o| notes.iter().map(move |n| n + 12 * o))
+            .collect();
+        match self.mode {
+            ArpMode::Down => pattern.reverse(),
+            // dont play the top and bottom twice
+            ArpMode::UpDown if pattern.len() > 2 => {
+                let back: Vec<i32> = pattern[1..pattern.len() - 1].iter().rev().copied().collect();
+                pattern.extend(back);
+            },
+            _ => {},
+        }
+        pattern
+    }
+
+    // every step that starts before until. steps that would have started before now
+    // (a long frame, a tempo change) get skipped rather than played late
+    pub fn run(&mut self, now: u64, until: u64) -> Vec<ArpNote> {
+        let mut out = vec![];
+        if self.played.is_empty() {
+            self.running = false;
+            return out;
+        }
+        if !self.running {
+            self.running = true;
+            self.next = now as f64;
+            self.step = 0;
+            self.pos = 0;
+        }
+        let len = self.step_len();
+        while self.next + len < now as f64 {
+            self.next += len;
+            self.step += 1;
+        }
+
+        let pattern = self.pattern();
+        loop {
+            let swing = if self.step % 2 == 1 { self.swing.clamp(0.0, 0.75) as f64 * len } else { 0.0 };
+            let on = self.next + swing;
+            if on >= until as f64 {
+                break;
+            }
+            let i = match self.mode {
+                ArpMode::Random => {
+                    self.seed = khash(self.seed.wrapping_add(self.step as u32).wrapping_mul(2654435761));
+                    self.seed as usize % pattern.len()
+                },
+                _ => self.pos % pattern.len(),
+            };
+            let gate = (self.gate.clamp(0.05, 1.0) as f64 * len).max(1.0);
+            out.push(ArpNote { note: pattern[i], on: on as u64, off: (on + gate) as u64 });
+            self.pos = (self.pos + 1) % pattern.len();
+            self.next += len;
+            self.step += 1;
+        }
+        out
+    }
+}
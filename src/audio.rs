@@ -1,15 +1,115 @@
 use crate::kmath::*;
+use crate::effects::*;
+use crate::effects::distortion::*;
+use crate::effects::formant::*;
+use crate::dsp::*;
+use crate::voices::*;
+use crate::voices::string::*;
+use crate::voices::osc::*;
+use crate::voices::morph::*;
+use crate::voices::sampler::*;
+use crate::voices::granular::*;
+use crate::voices::drums::*;
+use crate::voices::sfx::*;
+use crate::voices::click::*;
+use crate::priority_queue::*;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use ringbuf::Producer;
 
 
 // Audio system
-// PlayHold (UID, sd)
+// PlayHold (UID, slot, sd)
 // Release (UID)
+// voice UIDs come from VoiceHandle::next so nothing has to make them up
+// At (sample, command) holds a command back until that sample on the mixers clock
+// effects are addressed by their own UID, chosen by whoever adds them
+
+// samples per render call, the effects chain processes this many at once
+pub const BLOCK_SIZE: usize = 64;
+
+// instrument slots, each with its own voices, volume and pan
+pub const NUM_SLOTS: usize = 4;
+// voices per slot before we start stealing
+pub const MAX_VOICES: usize = 16;
+const MAX_PHASES: usize = 9 * 20; // voices knob * harmonics knob at the top
+// bus 0 is the dry bus every slot sends to by default, the rest are aux returns
+pub const NUM_BUSES: usize = 3;
+
+// which effects chain to put an insert on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainId {
+    Master,
+    Bus(usize),
+}
+
+// handed out when a note starts, keep it to release, retune or stop that note later
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VoiceHandle(pub u64);
+
+static NEXT_VOICE: AtomicU64 = AtomicU64::new(1);
+
+impl VoiceHandle {
+    // fine from any thread, ids never repeat
+    pub fn next() -> VoiceHandle {
+        VoiceHandle(NEXT_VOICE.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+// what At can schedule. kept flat so a timed note doesnt allocate or free anything on the audio thread
+#[derive(Debug, Clone)]
+pub enum Timed {
+    PlayHold(u64, usize, Box<SoundDesc>),
+    Release(u64),
+    Slide(u64, f32),
+    Click(bool),
+}
+
+// who scheduled a timed command, so whatever they left waiting can go at once
+pub const GROUP_NONE: u32 = 0;
+pub const GROUP_SEQ: u32 = 1;
+pub const GROUP_CLICK: u32 = 2;
+
+impl Timed {
+    fn command(self) -> AudioCommand {
+        match self {
+            Timed::PlayHold(id, slot, sd) => AudioCommand::PlayHold(id, slot, sd),
+            Timed::Release(id) => AudioCommand::Release(id),
+            Timed::Slide(id, f) => AudioCommand::Slide(id, f),
+            Timed::Click(accent) => AudioCommand::Click(accent),
+        }
+    }
+}
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum AudioCommand {
-    PlayHold(u64, SoundDesc),
+    PlayHold(u64, usize, Box<SoundDesc>),   // boxed on the GUI side, a whole SoundDesc would make every command huge
+    PlayFor(u64, usize, Box<SoundDesc>, f32),   // releases itself after this many seconds
     Release(u64),
+    Retune(u64, f32),                       // sfx voices keep their own pitch sweep and ignore this and Slide
+    Slide(u64, f32),                        // glide there over SLIDE_MS, 303 style
+    Stop(u64),                              // cut off now, no release tail
+    At(u64, u32, Timed),                    // time, group, command
+    Cancel(u32),                            // drop a groups timed commands, releases still happen now
+    Click(bool),                            // metronome, true for the first beat of the bar
+    SetTempo(f32),                          // for tempo synced effects
     SetVol(f32),
+    SetSlotVol(usize, f32),
+    SetSlotPan(usize, f32),
+    SetSlotGhost(usize, bool),
+    LoadSample(usize, Arc<Sample>),
+    SetSend(usize, usize, f32),     // slot, bus, level
+    SetBusFader(usize, f32),
+    SetBusMute(usize, bool),
+    SetBusSolo(usize, bool),
+
+    AddEffect(u64, ChainId, Box<dyn Effect>),
+    RemoveEffect(u64),
+    BypassEffect(u64, bool),
+    SetEffectParam(u64, usize, f32),
+    MoveEffect(u64, usize),
 }
 
 pub fn db_to_vol(db: f32) -> f32 {
@@ -37,49 +137,182 @@ pub struct SoundDesc {
     pub cdt: f32,
     pub cdr: f32,
     pub hard_clip: f32,
+
+    // drive 0 and bits 16 and downsample 1 are off
+    pub dist_curve: f32,
+    pub dist_drive: f32,
+    pub dist_bias: f32,
+    pub dist_os: f32,
+    pub crush_bits: f32,
+    pub crush_rate: f32,
+
+    // VoiceKind::from_param
+    pub voice: f32,
+    // string, excite 0 is all noise and 1 is all oscillator
+    pub str_damp: f32,
+    pub str_pick: f32,
+    pub str_excite: f32,
+
+    // vowel filter, mix 0 is off
+    pub fmt_vowel: f32,
+    pub fmt_rate: f32,
+    pub fmt_depth: f32,
+    pub fmt_mix: f32,
+
+    // second oscillator, mix 0 is just the additive one. sync over 0.5 is on
+    pub osc2_wave: f32,
+    pub osc2_semi: f32,
+    pub osc2_fine: f32,
+    pub osc2_mix: f32,
+    pub osc2_sync: f32,
+    pub osc2_ring: f32,
+    // square, 1 or 2 octaves down
+    pub sub_level: f32,
+    pub sub_oct: f32,
+
+    // keyframes for the morph voice
+    pub morph: MorphDesc,
+
+    // sampler, root is the frequency the sample plays back at its own speed.
+    // start and end are fractions of the sample, loop over 0.5 is on
+    pub smp_id: f32,
+    pub smp_root: f32,
+    pub smp_start: f32,
+    pub smp_end: f32,
+    pub smp_loop: f32,
+    pub smp_xfade: f32,
+
+    // granular, reads the same sample and root as the sampler
+    pub gr_size: f32,
+    pub gr_density: f32,
+    pub gr_pos: f32,
+    pub gr_pos_jit: f32,
+    pub gr_pitch_jit: f32,
+    pub gr_spray: f32,
+    pub gr_window: f32,
+    pub gr_scan: f32,
+
+    // drums, pitch is f like everything else
+    pub drum_kind: f32,
+    pub drum_decay: f32,
+    pub drum_tone: f32,
+    pub drum_punch: f32,
+
+    // sfxr params for the sfx voice
+    pub sfx: SfxParams,
+
+    // lowpass on every voice kind, cutoff at the top is off
+    pub flt_cut: f32,
+    pub flt_res: f32,
+
+    // 0..1 on top of amp
+    pub vel: f32,
+}
+
+pub const FILTER_OFF_HZ: f32 = 19999.0;
+
+impl SoundDesc {
+    fn sampler_params(&self) -> SamplerParams {
+        SamplerParams {
+            root: self.smp_root,
+            start: self.smp_start,
+            end: self.smp_end,
+            looping: self.smp_loop > 0.5,
+            xfade: self.smp_xfade,
+        }
+    }
+
+    fn drum_params(&self) -> DrumParams {
+        DrumParams {
+            kind: DrumKind::from_param(self.drum_kind),
+            decay: self.drum_decay,
+            tone: self.drum_tone,
+            punch: self.drum_punch,
+        }
+    }
+
+    fn grain_params(&self) -> GrainParams {
+        GrainParams {
+            root: self.smp_root,
+            size_ms: self.gr_size,
+            density: self.gr_density,
+            pos: self.gr_pos,
+            pos_jitter: self.gr_pos_jit,
+            pitch_jitter: self.gr_pitch_jit,
+            spray: self.gr_spray,
+            window: GrainWindow::from_param(self.gr_window),
+            scan: self.gr_scan,
+        }
+    }
 }
 
 
 
 pub struct Channel {
-    pub sd: SoundDesc,
+    pub sd: Box<SoundDesc>,
     pub birth: u64,
     pub age: u64,
     pub release_time: Option<u64>,
+    pub hold: Option<u64>,      // samples until it releases itself
+    pub slide: Option<f32>,     // frequency its gliding to
     pub phases: Vec<f32>,
+    pub gen: Generator,
     pub id: u64,
+    pub shaper: Waveshaper,
+    pub crusher: Crusher,
+    pub formant: FormantFilter,
+    pub fmt_lfo: Lfo,
+    pub filter: Biquad,
 }
 
 impl Channel {
+    // voices that end on their own, held or not
+    pub fn finished(&self) -> bool {
+        match &self.gen {
+            Generator::Drum(drum) => drum.finished(&self.sd.drum_params()),
+            Generator::Sfx(sfx) => sfx.finished(),
+            Generator::Morph(morph) => morph.finished(&self.sd.morph, self.age, self.release_time.map(|x| x - self.birth)),
+            _ => false,
+        }
+    }
+
     pub fn tick(&mut self) -> f32 {
         self.age += 1;
 
-        let sd = self.sd;
-
-        let voices_len = sd.voices.floor() as usize;
-        let n_len = sd.n.floor() as usize;
+        if let Some(target) = self.slide {
+            let c = EnvelopeFollower::coef(SLIDE_MS);
+            self.sd.f = target + (self.sd.f - target) * c;
+        }
+        let sd = *self.sd;
 
         // pre compression
-        let mut acc = 0.0;
+        let a_vol = db_to_vol(sd.amp) * sd.vel;
 
-        let a_vol = db_to_vol(sd.amp);
-
-        let a_env = env_amplitude(self.sd.ea, self.sd.ed, self.sd.es, self.sd.er, self.age, 44100, self.release_time.map(|x| x - self.birth));
-
-        let a_voices = 1.0 / voices_len as f32;
-
-        for detune_voice_num in 0..voices_len {
-            for n in 0..n_len {
-                let a_roll = 1.0 / ((n+1) as f32).powf(sd.troll);
+        let a_env = if self.gen.own_envelope() {
+            1.0
+        } else {
+            env_amplitude(self.sd.ea, self.sd.ed, self.sd.es, self.sd.er, self.age, 44100, self.release_time.map(|x| x - self.birth))
+        };
 
-                let detune_interval = 2.0f32.powf(sd.detune / 1200.0);
-                let f = sd.f * (n + 1) as f32;
-                let f = f * detune_interval.powf(detune_voice_num as f32);
+        let raw = match &mut self.gen {
+            Generator::Additive(analog) => analog_tick(&mut self.phases, analog, &sd),
+            Generator::String(string) => string.tick(sd.f),
+            Generator::Morph(morph) => morph.tick(sd.f, &sd.morph, self.age, self.release_time.map(|x| x - self.birth)),
+            Generator::Sampler(sampler) => sampler.tick(sd.f, &sd.sampler_params()),
+            Generator::Granular(granular) => granular.tick(sd.f, &sd.grain_params()),
+            Generator::Drum(drum) => drum.tick(sd.f, &sd.drum_params()),
+            Generator::Sfx(sfx) => sfx.tick(&sd.sfx),
+        };
+        let mut acc = raw * a_env * a_vol;
 
-                let idx = detune_voice_num * n_len + n;
-                self.phases[idx] = (self.phases[idx] + f / 44100.0).fract();
-                acc += a_voices * a_env * a_roll * a_vol * (2.0 * PI * self.phases[idx]).sin();
-            }
+        if sd.fmt_mix > 0.001 {
+            self.fmt_lfo.tick(sd.fmt_rate);
+            // same sweep as the formant effect
+            let morph = sd.fmt_vowel + self.fmt_lfo.triangle(0.25) * sd.fmt_depth;
+            acc = lerp(acc, self.formant.tick(acc, morph, 1.0), sd.fmt_mix);
+        }
+        if sd.flt_cut < FILTER_OFF_HZ {
+            acc = self.filter.tick(acc);
         }
 
 
@@ -101,7 +334,14 @@ impl Channel {
             }
         };
         
-        let out = comp;
+        let mut out = comp;
+
+        if sd.dist_drive > 0.01 {
+            out = self.shaper.tick(out, ShapeCurve::from_param(sd.dist_curve), sd.dist_drive, sd.dist_bias, sd.dist_os.round() as usize);
+        }
+        if sd.crush_bits < 15.99 || sd.crush_rate > 1.01 {
+            out = self.crusher.tick(out, sd.crush_bits, sd.crush_rate);
+        }
 
         // Hard clip
         let hc_vol = db_to_vol(self.sd.hard_clip);
@@ -118,81 +358,489 @@ impl Channel {
     }
 }
 
+// sum of the harmonics for every detuned voice, advances the phases
+fn additive_tick(phases: &mut [f32], sd: &SoundDesc) -> f32 {
+    let voices_len = sd.voices.floor() as usize;
+    let n_len = sd.n.floor() as usize;
+    let a_voices = 1.0 / voices_len as f32;
+    let detune_interval = 2.0f32.powf(sd.detune / 1200.0);
+
+    let mut acc = 0.0;
+    for detune_voice_num in 0..voices_len {
+        for n in 0..n_len {
+            let a_roll = 1.0 / ((n+1) as f32).powf(sd.troll);
+
+            let f = sd.f * (n + 1) as f32;
+            let f = f * detune_interval.powf(detune_voice_num as f32);
+
+            let idx = detune_voice_num * n_len + n;
+            phases[idx] = (phases[idx] + f / 44100.0).fract();
+            acc += a_voices * a_roll * (2.0 * PI * phases[idx]).sin();
+        }
+    }
+    acc
+}
+
+// additive oscillator plus the second oscillator and sub
+fn analog_tick(phases: &mut [f32], st: &mut AnalogState, sd: &SoundDesc) -> f32 {
+    let f2 = sd.f * 2.0f32.powf((sd.osc2_semi + sd.osc2_fine / 100.0) / 12.0);
+    let (o2, wrapped) = st.osc2.tick(f2, Wave::from_param(sd.osc2_wave));
+
+    // hard sync restarts the additive oscillator every time osc2 comes round
+    if sd.osc2_sync > 0.5 && wrapped {
+        for p in phases.iter_mut() {
+            *p = 0.0;
+        }
+    }
+    let main = additive_tick(phases, sd);
+
+    let mixed = lerp(main, o2, sd.osc2_mix);
+    let out = lerp(mixed, main * o2, sd.osc2_ring);
+
+    if sd.sub_level > 0.001 {
+        let sub_f = sd.f / 2.0f32.powf(sd.sub_oct.round().clamp(1.0, 2.0));
+        out + st.sub.tick(sub_f, Wave::Square).0 * sd.sub_level
+    } else {
+        out
+    }
+}
+
+// sample i of one period of the additive oscillator without detune, for exciting a string
+fn additive_period(sd: &SoundDesc, i: usize, len: usize) -> f32 {
+    let t = i as f32 / len as f32;
+    (0..sd.n.floor() as usize).map(|n| (2.0 * PI * (n + 1) as f32 * t).sin() / ((n+1) as f32).powf(sd.troll)).sum()
+}
+
+pub struct Slot {
+    pub vol: f32,
+    pub pan: f32,
+    pub sends: [f32; NUM_BUSES],    // post fader
+    pub ghost: bool,                // only feeds sidechain keys, never heard
+    pub channels: Vec<Channel>,
+    strings: Vec<KarplusString>,
+    morphs: Vec<Box<MorphOsc>>,
+    grains: Vec<Box<GranularVoice>>,
+    phases: Vec<Vec<f32>>,
+}
+
+impl Default for Slot {
+    fn default() -> Self {
+        let mut sends = [0.0; NUM_BUSES];
+        sends[0] = 1.0;
+        Slot {
+            vol: 1.0,
+            pan: 0.0,
+            sends,
+            ghost: false,
+            channels: Vec::with_capacity(MAX_VOICES),
+            strings: (0..MAX_VOICES).map(|_| KarplusString::default()).collect(),
+            morphs: (0..MAX_VOICES).map(|_| Box::new(MorphOsc::new(0))).collect(),
+            grains: (0..MAX_VOICES).map(|_| Box::new(GranularVoice::new(None, 0))).collect(),
+            phases: (0..MAX_VOICES).map(|_| Vec::with_capacity(MAX_PHASES)).collect(),
+        }
+    }
+}
+
+pub struct Bus {
+    pub chain: EffectChain,
+    pub fader: f32,
+    pub mute: bool,
+    pub solo: bool,
+}
+
+impl Default for Bus {
+    fn default() -> Self {
+        Bus {
+            chain: EffectChain::default(),
+            fader: 1.0,
+            mute: false,
+            solo: false,
+        }
+    }
+}
+
+impl Slot {
+    // steal the oldest, released ones first
+    fn make_room(&mut self) {
+        if self.channels.len() >= MAX_VOICES {
+            let victim = self.channels.iter().enumerate()
+                .max_by_key(|(_, c)| (c.release_time.is_some(), c.age))
+                .map(|(i, _)| i)
+                .unwrap();
+            self.remove(victim);
+        }
+    }
+
+    // strings, boxed generators and phases go back in the pool for the next note
+    fn remove(&mut self, i: usize) {
+        let c = self.channels.swap_remove(i);
+        self.phases.push(c.phases);
+        match c.gen {
+            Generator::String(string) => self.strings.push(string),
+            Generator::Morph(morph) => self.morphs.push(morph),
+            Generator::Granular(granular) => self.grains.push(granular),
+            _ => {},
+        }
+    }
+
+    // anything that allocates comes out of the pools, theres one of each for every voice so they cant run dry
+    fn generator(&mut self, sd: &SoundDesc, seed: u32, samples: &[Option<Arc<Sample>>]) -> Generator {
+        match VoiceKind::from_param(sd.voice) {
+            VoiceKind::Additive => Generator::Additive(AnalogState::default()),
+            VoiceKind::String => match self.strings.pop() {
+                Some(mut string) => {
+                    let len = (SAMPLE_RATE / sd.f.max(20.0)).ceil() as usize;
+                    string.pluck(sd.f, sd.str_damp, sd.str_pick, |i| lerp(noise_excitation(i, seed), additive_period(sd, i, len), sd.str_excite));
+                    Generator::String(string)
+                },
+                None => Generator::Additive(AnalogState::default()),
+            },
+            VoiceKind::Morph => match self.morphs.pop() {
+                Some(mut morph) => {
+                    *morph = MorphOsc::new(seed);
+                    Generator::Morph(morph)
+                },
+                None => Generator::Additive(AnalogState::default()),
+            },
+            VoiceKind::Sampler => {
+                let sample = samples.get(sd.smp_id.round() as usize).cloned().flatten();
+                Generator::Sampler(SamplerVoice::new(sample, &sd.sampler_params()))
+            },
+            VoiceKind::Granular => match self.grains.pop() {
+                Some(mut granular) => {
+                    let sample = samples.get(sd.smp_id.round() as usize).cloned().flatten();
+                    *granular = GranularVoice::new(sample, seed);
+                    Generator::Granular(granular)
+                },
+                None => Generator::Additive(AnalogState::default()),
+            },
+            VoiceKind::Drum => Generator::Drum(DrumVoice::new(sd.f, &sd.drum_params(), seed)),
+            VoiceKind::Sfx => Generator::Sfx(SfxSynth::new(&sd.sfx, seed)),
+        }
+    }
+
+    fn tick(&mut self) -> f32 {
+        let mut i = self.channels.len();
+        if i == 0 { return 0.0 }
+        i -= 1;
+        let mut acc = 0.0;
+        loop {
+            acc += self.channels[i].tick();
+            let c = &mut self.channels[i];
+            if c.release_time.is_none() && c.hold.is_some_and(|h| c.age >= h) {
+                c.release_time = Some(c.birth + c.age);
+            }
+            if self.channels[i].finished() {
+                self.remove(i);
+            } else if let Some(release_time) = self.channels[i].release_time {
+                let n = self.channels[i].age;
+                let n_since_release = n - release_time + self.channels[i].birth;
+                // morph voices go when their own release is done, not the slots
+                let morph = matches!(self.channels[i].gen, Generator::Morph(_));
+                if !morph && n_since_release > (self.channels[i].sd.er * 44100.0) as u64 {
+                    self.remove(i);
+                }
+            }
+
+            if i == 0 { break; }
+            i -= 1;
+        }
+        acc
+    }
+}
+
+// balance style, centre is unity on both sides
+pub fn pan_gains(pan: f32) -> (f32, f32) {
+    ((1.0 - pan).min(1.0), (1.0 + pan).min(1.0))
+}
+
 pub struct Mixer {
     pub out_vol: f32,
     pub sample_count: u64,
-    pub channels: Vec<Channel>,
+    pub slots: Vec<Slot>,
+    pub buses: Vec<Bus>,
+    pub master: EffectChain,
+    pub samples: Vec<Option<Arc<Sample>>>,
+    // post fader, read by the GUI off its local mixer
+    pub bus_meters: Vec<EnvelopeFollower>,
+    pub master_meter: EnvelopeFollower,
+    // sample_count as of the last block, for whoever is scheduling At commands
+    pub clock: Arc<AtomicU64>,
+    pub tempo: f32,
+    // removed effects go back to the GUI thread to be freed, big delay buffers and all
+    pub trash: Option<Producer<Box<dyn Effect>>>,
+
+    pending: PriorityQueue<u64, (u32, Timed)>,
+    clicks: Vec<ClickVoice>,
+    click_buf: [f32; BLOCK_SIZE],
+
+    bus_bufs: Vec<[(f32, f32); BLOCK_SIZE]>,
+    key_bufs: Vec<[(f32, f32); BLOCK_SIZE]>,
 }
 
+pub const SLIDE_MS: f32 = 60.0;
+
+// timed commands waiting on the audio thread, past this they just go in late
+pub const MAX_PENDING: usize = 1024;
+
+// more than this ringing at once and the new ones get dropped
+pub const MAX_CLICKS: usize = 8;
+pub const CLICK_VOL: f32 = 0.5;
+
+// meter ballistics
+pub const METER_ATTACK_MS: f32 = 1.0;
+pub const METER_RELEASE_MS: f32 = 300.0;
+
 impl Default for Mixer {
     fn default() -> Self {
         Mixer {
             out_vol: db_to_vol(-10.0),
             sample_count: 0,
-            channels: vec![],
+            samples: vec![None; NUM_SAMPLES],
+            slots: (0..NUM_SLOTS).map(|_| Slot::default()).collect(),
+            buses: (0..NUM_BUSES).map(|_| Bus::default()).collect(),
+            master: EffectChain::default(),
+            bus_meters: vec![EnvelopeFollower::default(); NUM_BUSES],
+            master_meter: EnvelopeFollower::default(),
+            clock: Arc::new(AtomicU64::new(0)),
+            tempo: 120.0,
+            pending: PriorityQueue::with_capacity(MAX_PENDING),
+            trash: None,
+            clicks: Vec::with_capacity(MAX_CLICKS),
+            click_buf: [0.0; BLOCK_SIZE],
+            bus_bufs: vec![[(0.0, 0.0); BLOCK_SIZE]; NUM_BUSES],
+            key_bufs: vec![[(0.0, 0.0); BLOCK_SIZE]; NUM_SLOTS],
         }
     }
 }
 
 impl Mixer {
-    // We assume only one playing at a time and unique
-    pub fn handle_command(&mut self, com: AudioCommand) {
-        println!("handle command {:?}", com);
+    fn chain(&mut self, chain: ChainId) -> Option<&mut EffectChain> {
+        match chain {
+            ChainId::Master => Some(&mut self.master),
+            ChainId::Bus(b) => self.buses.get_mut(b).map(|b| &mut b.chain),
+        }
+    }
+
+    // effect ids are unique across all the chains
+    fn chain_with(&mut self, id: u64) -> Option<&mut EffectChain> {
+        if self.master.inserts.iter().any(|x| x.id == id) {
+            return Some(&mut self.master);
+        }
+        self.buses.iter_mut().map(|b| &mut b.chain).find(|c| c.inserts.iter().any(|x| x.id == id))
+    }
+
+    // without a trash to send to (the GUIs local mixer) or with it full, its dropped here
+    fn discard(&mut self, effect: Box<dyn Effect>) {
+        if let Some(trash) = self.trash.as_mut() {
+            let _ = trash.push(effect);
+        }
+    }
 
+    fn start(&mut self, id: u64, slot: usize, sd: Box<SoundDesc>, hold: Option<u64>) {
+        let seed = khash(self.sample_count as u32);
+        let voices_len = sd.voices.floor() as usize;
+        let n_len = sd.n.floor() as usize;
+        if let Some(slot) = self.slots.get_mut(slot) {
+            slot.make_room();
+            let mut phases = slot.phases.pop().unwrap_or_default();
+            phases.clear();
+            for i in 0..voices_len {
+                for j in 0..n_len {
+                    phases.push(krand(seed + 13414177 * i as u32 + 123997 * j as u32) * 2.0 * PI)
+                }
+            }
+            let gen = slot.generator(&sd, seed, &self.samples);
+            let mut filter = Biquad::default();
+            filter.set_lowpass(sd.flt_cut, sd.flt_res);
+            slot.channels.push(Channel {
+                sd,
+                id,
+                age: 0,
+                birth: self.sample_count,
+                phases,
+                gen,
+                release_time: None,
+                hold,
+                slide: None,
+                shaper: Waveshaper::default(),
+                crusher: Crusher::default(),
+                formant: FormantFilter::default(),
+                fmt_lfo: Lfo::default(),
+                filter,
+            });
+        }
+    }
+
+    pub fn handle_command(&mut self, com: AudioCommand) {
         match com {
-            AudioCommand::PlayHold(id, sd) => {
-                let seed = khash(self.sample_count as u32);
-                let mut phases = vec![];
-                let voices_len = sd.voices.floor() as usize;
-                let n_len = sd.n.floor() as usize;
-                for i in 0..voices_len {
-                    for j in 0..n_len {
-                        phases.push(krand(seed + 13414177 * i as u32 + 123997 * j as u32) * 2.0 * PI)
+            AudioCommand::PlayHold(id, slot, sd) => self.start(id, slot, sd, None),
+            AudioCommand::PlayFor(id, slot, sd, dur) => self.start(id, slot, sd, Some((dur.max(0.0) * SAMPLE_RATE) as u64)),
+            AudioCommand::Release(id) => {
+                for slot in self.slots.iter_mut() {
+                    for channel in slot.channels.iter_mut() {
+                        if channel.id == id && channel.release_time.is_none() {
+                            channel.release_time = Some(self.sample_count);
+                        }
                     }
                 }
-                self.channels.push(Channel {
-                    sd,
-                    id,
-                    age: 0,
-                    birth: self.sample_count,
-                    phases,
-                    release_time: None,
-                })
             },
-            AudioCommand::Release(id) => {
-                for i in 0..self.channels.len() {
-                    if self.channels[i].id == id {
-                        self.channels[i].release_time = Some(self.sample_count);
+            AudioCommand::Retune(id, f) => {
+                for slot in self.slots.iter_mut() {
+                    for channel in slot.channels.iter_mut().filter(|c| c.id == id) {
+                        channel.sd.f = f;
+                    }
+                }
+            },
+            AudioCommand::Slide(id, f) => {
+                for slot in self.slots.iter_mut() {
+                    for channel in slot.channels.iter_mut().filter(|c| c.id == id) {
+                        channel.slide = Some(f);
+                    }
+                }
+            },
+            AudioCommand::Stop(id) => {
+                for slot in self.slots.iter_mut() {
+                    while let Some(i) = slot.channels.iter().position(|c| c.id == id) {
+                        slot.remove(i);
+                    }
+                }
+            },
+            AudioCommand::At(t, group, com) => {
+                if t <= self.sample_count || self.pending.len() >= MAX_PENDING {
+                    self.handle_command(com.command());
+                } else {
+                    self.pending.push(t, (group, com));
+                }
+            },
+            // swapped out so releases can go through handle_command, the empty one doesnt allocate
+            AudioCommand::Cancel(group) => {
+                let mut pending = std::mem::replace(&mut self.pending, PriorityQueue::new());
+                pending.retain(|_, (g, com)| {
+                    if *g != group {
+                        return true;
+                    }
+                    if let Timed::Release(id) = com {
+                        self.handle_command(AudioCommand::Release(*id));
                     }
+                    false
+                });
+                self.pending = pending;
+            },
+            AudioCommand::Click(accent) => if self.clicks.len() < MAX_CLICKS { self.clicks.push(ClickVoice::new(accent)) },
+            AudioCommand::SetTempo(bpm) => {
+                self.tempo = bpm;
+                self.master.set_tempo(bpm);
+                for bus in self.buses.iter_mut() {
+                    bus.chain.set_tempo(bpm);
                 }
             },
             AudioCommand::SetVol(v) => self.out_vol = v,
+            AudioCommand::SetSlotVol(slot, v) => if let Some(slot) = self.slots.get_mut(slot) { slot.vol = v },
+            AudioCommand::SetSlotPan(slot, p) => if let Some(slot) = self.slots.get_mut(slot) { slot.pan = p },
+            AudioCommand::SetSlotGhost(slot, g) => if let Some(slot) = self.slots.get_mut(slot) { slot.ghost = g },
+            // the old one is freed here if nothing is still playing it, not worth a trip back
+            AudioCommand::LoadSample(id, sample) => if id < NUM_SAMPLES { self.samples[id] = Some(sample) },
+            AudioCommand::SetSend(slot, bus, level) => if let Some(slot) = self.slots.get_mut(slot) {
+                if bus < NUM_BUSES { slot.sends[bus] = level }
+            },
+            AudioCommand::SetBusFader(bus, v) => if let Some(bus) = self.buses.get_mut(bus) { bus.fader = v },
+            AudioCommand::SetBusMute(bus, m) => if let Some(bus) = self.buses.get_mut(bus) { bus.mute = m },
+            AudioCommand::SetBusSolo(bus, s) => if let Some(bus) = self.buses.get_mut(bus) { bus.solo = s },
+            AudioCommand::AddEffect(id, chain, mut effect) => {
+                effect.set_tempo(self.tempo);
+                let rejected = match self.chain(chain) {
+                    Some(chain) => chain.add(id, effect),
+                    None => Some(effect),
+                };
+                if let Some(effect) = rejected {
+                    self.discard(effect);
+                }
+            },
+            AudioCommand::RemoveEffect(id) => {
+                if let Some(effect) = self.chain_with(id).and_then(|chain| chain.remove(id)) {
+                    self.discard(effect);
+                }
+            },
+            AudioCommand::BypassEffect(id, bypass) => if let Some(chain) = self.chain_with(id) { chain.set_bypass(id, bypass) },
+            AudioCommand::SetEffectParam(id, idx, val) => if let Some(chain) = self.chain_with(id) { chain.set_param(id, idx, val) },
+            AudioCommand::MoveEffect(id, idx) => if let Some(chain) = self.chain_with(id) { chain.move_to(id, idx) },
         }
     }
 
-    pub fn tick(&mut self) -> f32 {
-        self.sample_count += 1;
+    pub fn tick(&mut self) -> (f32, f32) {
+        let mut buf = [(0.0, 0.0); 1];
+        self.render(&mut buf);
+        buf[0]
+    }
 
-        let mut i = self.channels.len();
-        if i == 0 { return 0.0 }
-        i -= 1;
-        let mut acc = 0.0;
-        loop {
-            acc += self.channels[i].tick();
-            if let Some(release_time) = self.channels[i].release_time {
-                let n = self.channels[i].age;
-                let n_since_release = n - release_time + self.channels[i].birth;
-                if n_since_release > (self.channels[i].sd.er * 44100.0) as u64 {
-                    println!("removing {}, n since release {}, release samples: {}, n {} releasetime {}", i, n_since_release, (self.channels[i].sd.er * 44100.0) as u64, n, release_time);
-                    self.channels.swap_remove(i);
+    pub fn render(&mut self, buf: &mut [(f32, f32)]) {
+        for chunk in buf.chunks_mut(BLOCK_SIZE) {
+            self.render_block(chunk);
+        }
+    }
+
+    // voices are mono and summed per sample into their slot, slots get panned and sent to the buses,
+    // each bus runs its chain over the block and they all sum into the master chain
+    fn render_block(&mut self, buf: &mut [(f32, f32)]) {
+        let n = buf.len();
+        for bb in self.bus_bufs.iter_mut() {
+            for s in bb[..n].iter_mut() {
+                *s = (0.0, 0.0);
+            }
+        }
 
+        for i in 0..n {
+            self.sample_count += 1;
+            while self.pending.peek_key().is_some_and(|t| *t <= self.sample_count) {
+                let (_, (_, com)) = self.pending.pop().unwrap();
+                self.handle_command(com.command());
+            }
+            self.click_buf[i] = self.clicks.iter_mut().map(|c| c.tick()).sum();
+            for (slot, kb) in self.slots.iter_mut().zip(self.key_bufs.iter_mut()) {
+                let x = slot.tick();
+                let (gl, gr) = pan_gains(slot.pan);
+                kb[i] = (x * gl, x * gr);
+                if slot.ghost {
+                    continue;
+                }
+                let x = x * slot.vol;
+                for (bb, send) in self.bus_bufs.iter_mut().zip(slot.sends.iter()) {
+                    bb[i].0 += x * gl * send;
+                    bb[i].1 += x * gr * send;
                 }
             }
+        }
 
-            if i == 0 { break; }
-            i -= 1;
+        let any_solo = self.buses.iter().any(|b| b.solo);
+        for s in buf.iter_mut() {
+            *s = (0.0, 0.0);
+        }
+        let attack = EnvelopeFollower::coef(METER_ATTACK_MS);
+        let release = EnvelopeFollower::coef(METER_RELEASE_MS);
+        for ((bus, bb), meter) in self.buses.iter_mut().zip(self.bus_bufs.iter_mut()).zip(self.bus_meters.iter_mut()) {
+            // keep processing muted buses so tails carry on
+            bus.chain.process_block(&mut bb[..n], &self.key_bufs);
+            let audible = !(bus.mute || (any_solo && !bus.solo));
+            for (s, b) in buf.iter_mut().zip(bb.iter()) {
+                let (l, r) = if audible { (b.0 * bus.fader, b.1 * bus.fader) } else { (0.0, 0.0) };
+                meter.tick(l.abs().max(r.abs()), attack, release);
+                s.0 += l;
+                s.1 += r;
+            }
+        }
+
+        self.master.process_block(buf, &self.key_bufs);
+        for s in buf.iter() {
+            self.master_meter.tick(s.0.abs().max(s.1.abs()), attack, release);
+        }
+        // the metronome goes in after the master chain and meter
+        self.clicks.retain(|c| !c.finished());
+        for (s, c) in buf.iter_mut().zip(self.click_buf.iter()) {
+            s.0 = (s.0 + c * CLICK_VOL) * self.out_vol;
+            s.1 = (s.1 + c * CLICK_VOL) * self.out_vol;
         }
-        acc * self.out_vol
+        self.clock.store(self.sample_count, Ordering::Relaxed);
     }
 }
 
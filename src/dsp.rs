@@ -0,0 +1,223 @@
+use crate::kmath::*;
+
+// Small building blocks shared by the effects and voices
+
+pub const SAMPLE_RATE: f32 = 44100.0;
+
+pub fn ms_to_samples(ms: f32) -> f32 {
+    ms * 0.001 * SAMPLE_RATE
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct OnePole {
+    pub z: f32,
+}
+
+impl OnePole {
+    // coefficient for a given cutoff, compute it once rather than every sample if you can
+    pub fn coef(hz: f32) -> f32 {
+        1.0 - (-2.0 * PI * hz / SAMPLE_RATE).exp()
+    }
+
+    pub fn lowpass(&mut self, x: f32, coef: f32) -> f32 {
+        self.z += coef * (x - self.z);
+        self.z
+    }
+
+    pub fn highpass(&mut self, x: f32, coef: f32) -> f32 {
+        x - self.lowpass(x, coef)
+    }
+}
+
+#[derive(Clone)]
+pub struct DelayLine {
+    buf: Vec<f32>,
+    head: usize,
+}
+
+impl DelayLine {
+    pub fn new(len: usize) -> DelayLine {
+        DelayLine {
+            buf: vec![0.0; len.max(1)],
+            head: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.buf.iter_mut().for_each(|x| *x = 0.0);
+    }
+
+    pub fn push(&mut self, x: f32) {
+        self.head = (self.head + 1) % self.buf.len();
+        self.buf[self.head] = x;
+    }
+
+    // 0 is the most recently pushed sample
+    pub fn tap(&self, delay: usize) -> f32 {
+        let delay = delay.min(self.buf.len() - 1);
+        self.buf[(self.head + self.buf.len() - delay) % self.buf.len()]
+    }
+
+    pub fn set(&mut self, delay: usize, x: f32) {
+        let delay = delay.min(self.buf.len() - 1);
+        let i = (self.head + self.buf.len() - delay) % self.buf.len();
+        self.buf[i] = x;
+    }
+
+    // linear interpolated
+    pub fn tap_frac(&self, delay: f32) -> f32 {
+        let delay = delay.clamp(0.0, (self.buf.len() - 2) as f32);
+        let i = delay.floor();
+        lerp(self.tap(i as usize), self.tap(i as usize + 1), delay - i)
+    }
+}
+
+// phase runs 0..1, read it back with whatever shape and phase offset you want
+#[derive(Clone, Copy, Default)]
+pub struct Lfo {
+    pub phase: f32,
+}
+
+impl Lfo {
+    pub fn tick(&mut self, hz: f32) {
+        self.phase = (self.phase + hz / SAMPLE_RATE).fract();
+    }
+
+    // -1..1
+    pub fn sine(&self, offset: f32) -> f32 {
+        (2.0 * PI * (self.phase + offset)).sin()
+    }
+
+    // -1..1
+    pub fn triangle(&self, offset: f32) -> f32 {
+        let p = (self.phase + offset).fract();
+        4.0 * (p - 0.5).abs() - 1.0
+    }
+}
+
+// RBJ cookbook biquads. Setting new coefficients keeps the state so they can be swept
+#[derive(Clone, Copy)]
+pub struct Biquad {
+    pub b0: f32,
+    pub b1: f32,
+    pub b2: f32,
+    pub a1: f32,
+    pub a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl Default for Biquad {
+    fn default() -> Self {
+        Biquad { b0: 1.0, b1: 0.0, b2: 0.0, a1: 0.0, a2: 0.0, z1: 0.0, z2: 0.0 }
+    }
+}
+
+impl Biquad {
+    fn set(&mut self, b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) {
+        self.b0 = b0 / a0;
+        self.b1 = b1 / a0;
+        self.b2 = b2 / a0;
+        self.a1 = a1 / a0;
+        self.a2 = a2 / a0;
+    }
+
+    pub fn set_peaking(&mut self, f: f32, gain_db: f32, q: f32) {
+        let a = 10.0f32.powf(gain_db / 40.0);
+        let w = 2.0 * PI * f / SAMPLE_RATE;
+        let alpha = w.sin() / (2.0 * q);
+        self.set(1.0 + alpha * a, -2.0 * w.cos(), 1.0 - alpha * a, 1.0 + alpha / a, -2.0 * w.cos(), 1.0 - alpha / a);
+    }
+
+    pub fn set_low_shelf(&mut self, f: f32, gain_db: f32, q: f32) {
+        let a = 10.0f32.powf(gain_db / 40.0);
+        let w = 2.0 * PI * f / SAMPLE_RATE;
+        let (cw, alpha) = (w.cos(), w.sin() / (2.0 * q));
+        let k = 2.0 * a.sqrt() * alpha;
+        self.set(
+            a * ((a + 1.0) - (a - 1.0) * cw + k),
+            2.0 * a * ((a - 1.0) - (a + 1.0) * cw),
+            a * ((a + 1.0) - (a - 1.0) * cw - k),
+            (a + 1.0) + (a - 1.0) * cw + k,
+            -2.0 * ((a - 1.0) + (a + 1.0) * cw),
+            (a + 1.0) + (a - 1.0) * cw - k,
+        );
+    }
+
+    pub fn set_high_shelf(&mut self, f: f32, gain_db: f32, q: f32) {
+        let a = 10.0f32.powf(gain_db / 40.0);
+        let w = 2.0 * PI * f / SAMPLE_RATE;
+        let (cw, alpha) = (w.cos(), w.sin() / (2.0 * q));
+        let k = 2.0 * a.sqrt() * alpha;
+        self.set(
+            a * ((a + 1.0) + (a - 1.0) * cw + k),
+            -2.0 * a * ((a - 1.0) + (a + 1.0) * cw),
+            a * ((a + 1.0) + (a - 1.0) * cw - k),
+            (a + 1.0) - (a - 1.0) * cw + k,
+            2.0 * ((a - 1.0) - (a + 1.0) * cw),
+            (a + 1.0) - (a - 1.0) * cw - k,
+        );
+    }
+
+    pub fn set_lowpass(&mut self, f: f32, q: f32) {
+        let w = 2.0 * PI * f / SAMPLE_RATE;
+        let (cw, alpha) = (w.cos(), w.sin() / (2.0 * q));
+        self.set((1.0 - cw) / 2.0, 1.0 - cw, (1.0 - cw) / 2.0, 1.0 + alpha, -2.0 * cw, 1.0 - alpha);
+    }
+
+    pub fn set_highpass(&mut self, f: f32, q: f32) {
+        let w = 2.0 * PI * f / SAMPLE_RATE;
+        let (cw, alpha) = (w.cos(), w.sin() / (2.0 * q));
+        self.set((1.0 + cw) / 2.0, -(1.0 + cw), (1.0 + cw) / 2.0, 1.0 + alpha, -2.0 * cw, 1.0 - alpha);
+    }
+
+    // constant 0db peak gain
+    pub fn set_bandpass(&mut self, f: f32, q: f32) {
+        let w = 2.0 * PI * f / SAMPLE_RATE;
+        let (cw, alpha) = (w.cos(), w.sin() / (2.0 * q));
+        self.set(alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cw, 1.0 - alpha);
+    }
+
+    // transposed direct form 2
+    pub fn tick(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+
+    // |H| at f, for drawing
+    pub fn magnitude_db(&self, f: f32) -> f32 {
+        let w = 2.0 * PI * f / SAMPLE_RATE;
+        let z1 = Vec2::new(w.cos(), -w.sin());
+        let z2 = z1.complex_mul(z1);
+        let num = Vec2::new(self.b0, 0.0) + z1 * self.b1 + z2 * self.b2;
+        let den = Vec2::new(1.0, 0.0) + z1 * self.a1 + z2 * self.a2;
+        20.0 * (num.magnitude() / den.magnitude()).log10()
+    }
+}
+
+// Peak follower with separate attack and release, the meters and the compressor both use this
+// so a meter reading lines up with what the compressor is reacting to
+#[derive(Clone, Copy, Default)]
+pub struct EnvelopeFollower {
+    pub env: f32,
+}
+
+impl EnvelopeFollower {
+    pub fn coef(ms: f32) -> f32 {
+        (-1.0 / ms_to_samples(ms).max(1.0)).exp()
+    }
+
+    // coefs from EnvelopeFollower::coef
+    pub fn tick(&mut self, x: f32, attack: f32, release: f32) -> f32 {
+        let x = x.abs();
+        let c = if x > self.env { attack } else { release };
+        self.env = x + c * (self.env - x);
+        self.env
+    }
+}
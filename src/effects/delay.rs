@@ -0,0 +1,133 @@
+use crate::effects::*;
+use crate::dsp::*;
+
+// longest delay we allocate for, in seconds
+const MAX_DELAY: f32 = 4.0;
+
+// note lengths for sync, in beats. the tempo comes from the transport
+const DIVISIONS: [f32; 7] = [0.25, 0.5, 0.75, 1.0, 1.5, 2.0, 4.0];
+pub const DIVISION_NAMES: [&str; 7] = ["1/16", "1/8", "1/8.", "1/4", "1/4.", "1/2", "1/1"];
+
+pub const TIME: usize = 0;
+pub const SYNC: usize = 1;
+pub const DIV: usize = 2;
+pub const FEEDBACK: usize = 3;
+pub const HIGH_CUT: usize = 4;
+pub const LOW_CUT: usize = 5;
+pub const PING_PONG: usize = 6;
+pub const MIX: usize = 7;
+
+// on or off, the GUI gives these buttons rather than knobs
+pub const TOGGLES: [usize; 2] = [SYNC, PING_PONG];
+
+static PARAMS: [ParamDesc; 8] = [
+    ParamDesc { name: "time ms", min: 1.0, max: 2000.0, default: 350.0 },
+    ParamDesc { name: "sync", min: 0.0, max: 1.0, default: 0.0 },
+    ParamDesc { name: "division", min: 0.0, max: 6.0, default: 3.0 },
+    ParamDesc { name: "feedback", min: 0.0, max: 0.95, default: 0.4 },
+    ParamDesc { name: "high cut", min: 500.0, max: 20000.0, default: 8000.0 },
+    ParamDesc { name: "low cut", min: 20.0, max: 2000.0, default: 100.0 },
+    ParamDesc { name: "ping pong", min: 0.0, max: 1.0, default: 0.0 },
+    ParamDesc { name: "mix", min: 0.0, max: 1.0, default: 0.3 },
+];
+
+#[derive(Clone)]
+pub struct Delay {
+    params: [f32; 8],
+    bpm: f32,
+
+    l: DelayLine,
+    r: DelayLine,
+    lp: [OnePole; 2],
+    hp: [OnePole; 2],
+    lp_coef: f32,
+    hp_coef: f32,
+
+    // smoothed so turning the time knob doesnt crackle
+    delay_samples: f32,
+}
+
+impl Default for Delay {
+    fn default() -> Self {
+        let len = (MAX_DELAY * SAMPLE_RATE) as usize;
+        let mut d = Delay {
+            params: [0.0; 8],
+            bpm: 120.0,
+            l: DelayLine::new(len),
+            r: DelayLine::new(len),
+            lp: [OnePole::default(); 2],
+            hp: [OnePole::default(); 2],
+            lp_coef: 0.0,
+            hp_coef: 0.0,
+            delay_samples: 0.0,
+        };
+        for (i, p) in PARAMS.iter().enumerate() {
+            d.set_param(i, p.default);
+        }
+        d.delay_samples = d.target_samples();
+        d
+    }
+}
+
+impl Delay {
+    fn target_samples(&self) -> f32 {
+        let ms = if self.params[SYNC] > 0.5 {
+            let beats = DIVISIONS[self.params[DIV].round() as usize];
+            beats * 60000.0 / self.bpm
+        } else {
+            self.params[TIME]
+        };
+        ms_to_samples(ms).min((self.l.len() - 2) as f32)
+    }
+}
+
+impl Effect for Delay {
+    fn name(&self) -> &'static str { "delay" }
+    fn params(&self) -> &'static [ParamDesc] { &PARAMS }
+
+    fn get_param(&self, idx: usize) -> f32 {
+        self.params.get(idx).copied().unwrap_or(0.0)
+    }
+
+    fn set_param(&mut self, idx: usize, val: f32) {
+        if idx >= PARAMS.len() { return; }
+        self.params[idx] = val.clamp(PARAMS[idx].min, PARAMS[idx].max);
+        match idx {
+            HIGH_CUT => self.lp_coef = OnePole::coef(self.params[HIGH_CUT]),
+            LOW_CUT => self.hp_coef = OnePole::coef(self.params[LOW_CUT]),
+            _ => {},
+        }
+    }
+
+    fn set_tempo(&mut self, bpm: f32) {
+        self.bpm = bpm.max(1.0);
+    }
+
+    fn process(&mut self, l: f32, r: f32) -> (f32, f32) {
+        self.delay_samples += 0.001 * (self.target_samples() - self.delay_samples);
+
+        let wet_l = self.l.tap_frac(self.delay_samples);
+        let wet_r = self.r.tap_frac(self.delay_samples);
+
+        // filters live in the feedback path so each repeat gets darker and thinner
+        let fb = self.params[FEEDBACK];
+        let fb_l = self.hp[0].highpass(self.lp[0].lowpass(wet_l, self.lp_coef), self.hp_coef) * fb;
+        let fb_r = self.hp[1].highpass(self.lp[1].lowpass(wet_r, self.lp_coef), self.hp_coef) * fb;
+
+        if self.params[PING_PONG] > 0.5 {
+            // mono in on the left, repeats bounce across
+            self.l.push(0.5 * (l + r) + fb_r);
+            self.r.push(fb_l);
+        } else {
+            self.l.push(l + fb_l);
+            self.r.push(r + fb_r);
+        }
+
+        let mix = self.params[MIX];
+        (l * (1.0 - mix) + wet_l * mix, r * (1.0 - mix) + wet_r * mix)
+    }
+
+    fn box_clone(&self) -> Box<dyn Effect> {
+        Box::new(self.clone())
+    }
+}
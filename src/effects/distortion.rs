@@ -0,0 +1,210 @@
+use crate::effects::*;
+use crate::dsp::*;
+use crate::audio::*;
+use crate::kmath::*;
+
+// Waveshaper and bitcrusher. The mono cores are used per voice by Channel,
+// the Effect wrappers run one per side on the master bus.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShapeCurve {
+    Tanh,
+    Foldback,
+    Tube,
+    HardClip,
+}
+
+impl ShapeCurve {
+    // knobs are floats
+    pub fn from_param(x: f32) -> ShapeCurve {
+        match x.round() as i32 {
+            1 => ShapeCurve::Foldback,
+            2 => ShapeCurve::Tube,
+            3 => ShapeCurve::HardClip,
+            _ => ShapeCurve::Tanh,
+        }
+    }
+
+    pub fn apply(&self, x: f32) -> f32 {
+        match self {
+            ShapeCurve::Tanh => x.tanh(),
+            ShapeCurve::Foldback => {
+                // reflect back off +-1 as many times as it takes
+                let t = (x + 1.0).rem_euclid(4.0);
+                if t < 2.0 { t - 1.0 } else { 3.0 - t }
+            },
+            ShapeCurve::Tube => {
+                // positive side saturates harder than the negative
+                if x >= 0.0 { 1.0 - (-x).exp() } else { (x * 0.7).tanh() / 0.7f32.tanh() * 0.9 }
+            },
+            ShapeCurve::HardClip => x.clamp(-1.0, 1.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct Waveshaper {
+    prev: f32,
+    aa: [OnePole; 2],
+    dc: OnePole,
+}
+
+impl Waveshaper {
+    // drive in db, oversample is how many times to run the curve per sample
+    pub fn tick(&mut self, x: f32, curve: ShapeCurve, drive: f32, bias: f32, oversample: usize) -> f32 {
+        let g = db_to_vol(drive);
+        let os = oversample.max(1);
+
+        // linear interpolate up, shape, lowpass at the old nyquist and average back down
+        let aa_coef = 1.0 - (-2.0 * PI * 0.45 * SAMPLE_RATE / (SAMPLE_RATE * os as f32)).exp();
+        let offset = curve.apply(bias);
+        let mut acc = 0.0;
+        for k in 1..=os {
+            let xi = lerp(self.prev, x, k as f32 / os as f32);
+            let yi = curve.apply(g * xi + bias) - offset;
+            if os > 1 {
+                let y0 = self.aa[0].lowpass(yi, aa_coef);
+                acc += self.aa[1].lowpass(y0, aa_coef);
+            } else {
+                acc += yi;
+            }
+        }
+        self.prev = x;
+
+        // bias leaves dc behind
+        self.dc.highpass(acc / os as f32, OnePole::coef(10.0))
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct Crusher {
+    counter: f32,
+    held: f32,
+}
+
+impl Crusher {
+    // factor is how many samples to hold each one for, can be fractional
+    pub fn tick(&mut self, x: f32, bits: f32, factor: f32) -> f32 {
+        self.counter += 1.0;
+        if self.counter >= factor {
+            self.counter -= factor;
+            let step = 2.0 / 2.0f32.powf(bits);
+            self.held = (x / step).round() * step;
+        }
+        self.held
+    }
+}
+
+const CURVE: usize = 0;
+const DRIVE: usize = 1;
+const BIAS: usize = 2;
+const OVERSAMPLE: usize = 3;
+const MIX: usize = 4;
+
+static DISTORTION_PARAMS: [ParamDesc; 5] = [
+    ParamDesc { name: "curve", min: 0.0, max: 3.0, default: 0.0 },
+    ParamDesc { name: "drive db", min: 0.0, max: 40.0, default: 12.0 },
+    ParamDesc { name: "bias", min: -1.0, max: 1.0, default: 0.0 },
+    ParamDesc { name: "oversample", min: 1.0, max: 8.0, default: 4.0 },
+    ParamDesc { name: "mix", min: 0.0, max: 1.0, default: 1.0 },
+];
+
+#[derive(Clone)]
+pub struct Distortion {
+    params: [f32; 5],
+    shapers: [Waveshaper; 2],
+}
+
+impl Default for Distortion {
+    fn default() -> Self {
+        let mut d = Distortion {
+            params: [0.0; 5],
+            shapers: [Waveshaper::default(); 2],
+        };
+        for (i, p) in DISTORTION_PARAMS.iter().enumerate() {
+            d.params[i] = p.default;
+        }
+        d
+    }
+}
+
+impl Effect for Distortion {
+    fn name(&self) -> &'static str { "distortion" }
+    fn params(&self) -> &'static [ParamDesc] { &DISTORTION_PARAMS }
+
+    fn get_param(&self, idx: usize) -> f32 {
+        self.params.get(idx).copied().unwrap_or(0.0)
+    }
+
+    fn set_param(&mut self, idx: usize, val: f32) {
+        if idx >= DISTORTION_PARAMS.len() { return; }
+        self.params[idx] = val.clamp(DISTORTION_PARAMS[idx].min, DISTORTION_PARAMS[idx].max);
+    }
+
+    fn process(&mut self, l: f32, r: f32) -> (f32, f32) {
+        let curve = ShapeCurve::from_param(self.params[CURVE]);
+        let os = self.params[OVERSAMPLE].round() as usize;
+        let wl = self.shapers[0].tick(l, curve, self.params[DRIVE], self.params[BIAS], os);
+        let wr = self.shapers[1].tick(r, curve, self.params[DRIVE], self.params[BIAS], os);
+        let mix = self.params[MIX];
+        (l * (1.0 - mix) + wl * mix, r * (1.0 - mix) + wr * mix)
+    }
+
+    fn box_clone(&self) -> Box<dyn Effect> {
+        Box::new(self.clone())
+    }
+}
+
+const BITS: usize = 0;
+const DOWNSAMPLE: usize = 1;
+const CRUSH_MIX: usize = 2;
+
+static BITCRUSHER_PARAMS: [ParamDesc; 3] = [
+    ParamDesc { name: "bits", min: 1.0, max: 16.0, default: 8.0 },
+    ParamDesc { name: "downsample", min: 1.0, max: 64.0, default: 4.0 },
+    ParamDesc { name: "mix", min: 0.0, max: 1.0, default: 1.0 },
+];
+
+#[derive(Clone)]
+pub struct Bitcrusher {
+    params: [f32; 3],
+    crushers: [Crusher; 2],
+}
+
+impl Default for Bitcrusher {
+    fn default() -> Self {
+        let mut b = Bitcrusher {
+            params: [0.0; 3],
+            crushers: [Crusher::default(); 2],
+        };
+        for (i, p) in BITCRUSHER_PARAMS.iter().enumerate() {
+            b.params[i] = p.default;
+        }
+        b
+    }
+}
+
+impl Effect for Bitcrusher {
+    fn name(&self) -> &'static str { "bitcrusher" }
+    fn params(&self) -> &'static [ParamDesc] { &BITCRUSHER_PARAMS }
+
+    fn get_param(&self, idx: usize) -> f32 {
+        self.params.get(idx).copied().unwrap_or(0.0)
+    }
+
+    fn set_param(&mut self, idx: usize, val: f32) {
+        if idx >= BITCRUSHER_PARAMS.len() { return; }
+        self.params[idx] = val.clamp(BITCRUSHER_PARAMS[idx].min, BITCRUSHER_PARAMS[idx].max);
+    }
+
+    fn process(&mut self, l: f32, r: f32) -> (f32, f32) {
+        let wl = self.crushers[0].tick(l, self.params[BITS], self.params[DOWNSAMPLE]);
+        let wr = self.crushers[1].tick(r, self.params[BITS], self.params[DOWNSAMPLE]);
+        let mix = self.params[CRUSH_MIX];
+        (l * (1.0 - mix) + wl * mix, r * (1.0 - mix) + wr * mix)
+    }
+
+    fn box_clone(&self) -> Box<dyn Effect> {
+        Box::new(self.clone())
+    }
+}
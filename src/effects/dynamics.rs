@@ -0,0 +1,89 @@
+use crate::effects::*;
+use crate::audio::*;
+use crate::dsp::*;
+
+// Feed forward compressor. Key 0 listens to its own input, key n listens to slot n instead
+// (pre fader, so a ghost slot works as a trigger only key).
+const THRESHOLD: usize = 0;
+const RATIO: usize = 1;
+const ATTACK: usize = 2;
+const RELEASE: usize = 3;
+const MAKEUP: usize = 4;
+const KEY: usize = 5;
+
+static PARAMS: [ParamDesc; 6] = [
+    ParamDesc { name: "thresh", min: -60.0, max: 0.0, default: -20.0 },
+    ParamDesc { name: "ratio", min: 1.0, max: 20.0, default: 4.0 },
+    ParamDesc { name: "attack", min: 0.1, max: 100.0, default: 5.0 },
+    ParamDesc { name: "release", min: 10.0, max: 1000.0, default: 150.0 },
+    ParamDesc { name: "makeup", min: 0.0, max: 24.0, default: 0.0 },
+    ParamDesc { name: "key", min: 0.0, max: NUM_SLOTS as f32, default: 0.0 },
+];
+
+#[derive(Clone)]
+pub struct Compressor {
+    params: [f32; 6],
+    follower: EnvelopeFollower,
+}
+
+impl Default for Compressor {
+    fn default() -> Self {
+        let mut params = [0.0; 6];
+        for (i, p) in PARAMS.iter().enumerate() {
+            params[i] = p.default;
+        }
+        Compressor {
+            params,
+            follower: EnvelopeFollower::default(),
+        }
+    }
+}
+
+impl Compressor {
+    fn gain(&mut self, key: f32, attack: f32, release: f32) -> f32 {
+        let env = self.follower.tick(key, attack, release);
+        let over = vol_to_db(env.max(1e-6)) - self.params[THRESHOLD];
+        let reduction = if over > 0.0 { over * (1.0 - 1.0 / self.params[RATIO]) } else { 0.0 };
+        db_to_vol(self.params[MAKEUP] - reduction)
+    }
+}
+
+impl Effect for Compressor {
+    fn name(&self) -> &'static str { "compressor" }
+    fn params(&self) -> &'static [ParamDesc] { &PARAMS }
+
+    fn get_param(&self, idx: usize) -> f32 {
+        self.params.get(idx).copied().unwrap_or(0.0)
+    }
+
+    fn set_param(&mut self, idx: usize, val: f32) {
+        if idx >= PARAMS.len() { return; }
+        self.params[idx] = val.clamp(PARAMS[idx].min, PARAMS[idx].max);
+    }
+
+    fn process(&mut self, l: f32, r: f32) -> (f32, f32) {
+        let attack = EnvelopeFollower::coef(self.params[ATTACK]);
+        let release = EnvelopeFollower::coef(self.params[RELEASE]);
+        let g = self.gain(l.abs().max(r.abs()), attack, release);
+        (l * g, r * g)
+    }
+
+    fn sidechain(&self) -> Option<usize> {
+        let key = self.params[KEY].round() as usize;
+        if key == 0 { None } else { Some(key - 1) }
+    }
+
+    fn process_block_keyed(&mut self, buf: &mut [(f32, f32)], key: &[(f32, f32)]) {
+        let attack = EnvelopeFollower::coef(self.params[ATTACK]);
+        let release = EnvelopeFollower::coef(self.params[RELEASE]);
+        for (s, k) in buf.iter_mut().zip(key.iter()) {
+            let g = self.gain(k.0.abs().max(k.1.abs()), attack, release);
+            s.0 *= g;
+            s.1 *= g;
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn Effect> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,103 @@
+use crate::effects::*;
+use crate::dsp::*;
+
+// Low shelf, three peaks and a high shelf. Params go freq, gain, q for each band in order.
+
+pub const EQ_BANDS: usize = 5;
+
+static PARAMS: [ParamDesc; 15] = [
+    ParamDesc { name: "low hz", min: 20.0, max: 20000.0, default: 100.0 },
+    ParamDesc { name: "low db", min: -24.0, max: 24.0, default: 0.0 },
+    ParamDesc { name: "low q", min: 0.3, max: 4.0, default: 0.707 },
+    ParamDesc { name: "1 hz", min: 20.0, max: 20000.0, default: 400.0 },
+    ParamDesc { name: "1 db", min: -24.0, max: 24.0, default: 0.0 },
+    ParamDesc { name: "1 q", min: 0.1, max: 16.0, default: 1.0 },
+    ParamDesc { name: "2 hz", min: 20.0, max: 20000.0, default: 1500.0 },
+    ParamDesc { name: "2 db", min: -24.0, max: 24.0, default: 0.0 },
+    ParamDesc { name: "2 q", min: 0.1, max: 16.0, default: 1.0 },
+    ParamDesc { name: "3 hz", min: 20.0, max: 20000.0, default: 5000.0 },
+    ParamDesc { name: "3 db", min: -24.0, max: 24.0, default: 0.0 },
+    ParamDesc { name: "3 q", min: 0.1, max: 16.0, default: 1.0 },
+    ParamDesc { name: "high hz", min: 20.0, max: 20000.0, default: 8000.0 },
+    ParamDesc { name: "high db", min: -24.0, max: 24.0, default: 0.0 },
+    ParamDesc { name: "high q", min: 0.3, max: 4.0, default: 0.707 },
+];
+
+// coefficients for one band from the full param list
+pub fn eq_band(band: usize, params: &[f32]) -> Biquad {
+    let (f, g, q) = (params[band * 3], params[band * 3 + 1], params[band * 3 + 2]);
+    let mut bq = Biquad::default();
+    match band {
+        0 => bq.set_low_shelf(f, g, q),
+        4 => bq.set_high_shelf(f, g, q),
+        _ => bq.set_peaking(f, g, q),
+    }
+    bq
+}
+
+// combined response of all the bands
+pub fn eq_response_db(params: &[f32], f: f32) -> f32 {
+    (0..EQ_BANDS).map(|b| eq_band(b, params).magnitude_db(f)).sum()
+}
+
+#[derive(Clone)]
+pub struct ParametricEq {
+    params: [f32; 15],
+    bands: [[Biquad; 2]; EQ_BANDS],
+}
+
+impl Default for ParametricEq {
+    fn default() -> Self {
+        let mut eq = ParametricEq {
+            params: [0.0; 15],
+            bands: [[Biquad::default(); 2]; EQ_BANDS],
+        };
+        for (i, p) in PARAMS.iter().enumerate() {
+            eq.params[i] = p.default;
+        }
+        for b in 0..EQ_BANDS {
+            eq.update_band(b);
+        }
+        eq
+    }
+}
+
+impl ParametricEq {
+    fn update_band(&mut self, band: usize) {
+        let bq = eq_band(band, &self.params);
+        for side in self.bands[band].iter_mut() {
+            side.b0 = bq.b0;
+            side.b1 = bq.b1;
+            side.b2 = bq.b2;
+            side.a1 = bq.a1;
+            side.a2 = bq.a2;
+        }
+    }
+}
+
+impl Effect for ParametricEq {
+    fn name(&self) -> &'static str { "eq" }
+    fn params(&self) -> &'static [ParamDesc] { &PARAMS }
+
+    fn get_param(&self, idx: usize) -> f32 {
+        self.params.get(idx).copied().unwrap_or(0.0)
+    }
+
+    fn set_param(&mut self, idx: usize, val: f32) {
+        if idx >= PARAMS.len() { return; }
+        self.params[idx] = val.clamp(PARAMS[idx].min, PARAMS[idx].max);
+        self.update_band(idx / 3);
+    }
+
+    fn process(&mut self, mut l: f32, mut r: f32) -> (f32, f32) {
+        for band in self.bands.iter_mut() {
+            l = band[0].tick(l);
+            r = band[1].tick(r);
+        }
+        (l, r)
+    }
+
+    fn box_clone(&self) -> Box<dyn Effect> {
+        Box::new(self.clone())
+    }
+}
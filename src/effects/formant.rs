@@ -0,0 +1,136 @@
+use crate::effects::*;
+use crate::dsp::*;
+use crate::kmath::*;
+use crate::audio::*;
+
+// Vowel filter, three bandpasses in parallel at the first three formants.
+// Morph runs 0..4 through A E I O U and blends the neighbours in between.
+
+// freq, bandwidth, gain db for each formant
+const VOWELS: [[(f32, f32, f32); 3]; 5] = [
+    [(800.0, 80.0, 0.0), (1150.0, 90.0, -4.0), (2900.0, 120.0, -20.0)],   // A
+    [(400.0, 60.0, 0.0), (1600.0, 80.0, -10.0), (2700.0, 120.0, -20.0)],  // E
+    [(350.0, 50.0, 0.0), (1700.0, 100.0, -14.0), (2700.0, 120.0, -20.0)], // I
+    [(450.0, 70.0, 0.0), (800.0, 80.0, -9.0), (2830.0, 100.0, -16.0)],    // O
+    [(325.0, 50.0, 0.0), (700.0, 60.0, -12.0), (2530.0, 170.0, -24.0)],   // U
+];
+
+pub const NUM_VOWELS: usize = 5;
+
+// only recompute the coefficients when the morph has moved this much
+const MORPH_EPS: f32 = 0.002;
+
+// the bandpasses are narrow so the sum comes out quiet
+const MAKEUP: f32 = 2.0;
+
+#[derive(Clone, Copy)]
+pub struct FormantFilter {
+    bands: [Biquad; 3],
+    gains: [f32; 3],
+    last: (f32, f32),
+}
+
+impl Default for FormantFilter {
+    fn default() -> Self {
+        FormantFilter {
+            bands: [Biquad::default(); 3],
+            gains: [0.0; 3],
+            last: (-1.0, -1.0),
+        }
+    }
+}
+
+impl FormantFilter {
+    // morph 0..4, res scales the q of every band
+    fn set(&mut self, morph: f32, res: f32) {
+        let morph = morph.clamp(0.0, (NUM_VOWELS - 1) as f32);
+        if (morph - self.last.0).abs() < MORPH_EPS && (res - self.last.1).abs() < MORPH_EPS {
+            return;
+        }
+        self.last = (morph, res);
+
+        let i = (morph.floor() as usize).min(NUM_VOWELS - 2);
+        let t = morph - i as f32;
+        let pairs = VOWELS[i].iter().zip(VOWELS[i + 1].iter());
+        for ((band, gain), (&(f0, bw0, g0), &(f1, bw1, g1))) in self.bands.iter_mut().zip(self.gains.iter_mut()).zip(pairs) {
+            // frequencies blend in log so the glide sounds even
+            let f = f0 * (f1 / f0).powf(t);
+            let q = f / lerp(bw0, bw1, t) * res;
+            band.set_bandpass(f, q);
+            *gain = db_to_vol(lerp(g0, g1, t));
+        }
+    }
+
+    pub fn tick(&mut self, x: f32, morph: f32, res: f32) -> f32 {
+        self.set(morph, res);
+        let mut acc = 0.0;
+        for (band, g) in self.bands.iter_mut().zip(self.gains.iter()) {
+            acc += band.tick(x) * g;
+        }
+        acc * MAKEUP
+    }
+}
+
+const VOWEL: usize = 0;
+const RATE: usize = 1;
+const DEPTH: usize = 2;
+const RES: usize = 3;
+const MIX: usize = 4;
+
+static PARAMS: [ParamDesc; 5] = [
+    ParamDesc { name: "vowel", min: 0.0, max: (NUM_VOWELS - 1) as f32, default: 0.0 },
+    ParamDesc { name: "rate", min: 0.0, max: 8.0, default: 0.0 },
+    ParamDesc { name: "depth", min: 0.0, max: 2.0, default: 1.0 },
+    ParamDesc { name: "res", min: 0.5, max: 4.0, default: 1.0 },
+    ParamDesc { name: "mix", min: 0.0, max: 1.0, default: 1.0 },
+];
+
+#[derive(Clone)]
+pub struct Formant {
+    params: [f32; 5],
+    lfo: Lfo,
+    filters: [FormantFilter; 2],
+}
+
+impl Default for Formant {
+    fn default() -> Self {
+        let mut params = [0.0; 5];
+        for (i, p) in PARAMS.iter().enumerate() {
+            params[i] = p.default;
+        }
+        Formant {
+            params,
+            lfo: Lfo::default(),
+            filters: [FormantFilter::default(); 2],
+        }
+    }
+}
+
+impl Effect for Formant {
+    fn name(&self) -> &'static str { "formant" }
+    fn params(&self) -> &'static [ParamDesc] { &PARAMS }
+
+    fn get_param(&self, idx: usize) -> f32 {
+        self.params.get(idx).copied().unwrap_or(0.0)
+    }
+
+    fn set_param(&mut self, idx: usize, val: f32) {
+        if idx >= PARAMS.len() { return; }
+        self.params[idx] = val.clamp(PARAMS[idx].min, PARAMS[idx].max);
+    }
+
+    fn process(&mut self, l: f32, r: f32) -> (f32, f32) {
+        self.lfo.tick(self.params[RATE]);
+        // a quarter in so the sweep starts on the vowel the knob says
+        let morph = self.params[VOWEL] + self.lfo.triangle(0.25) * self.params[DEPTH];
+        let res = self.params[RES];
+        let wl = self.filters[0].tick(l, morph, res);
+        let wr = self.filters[1].tick(r, morph, res);
+        let mix = self.params[MIX];
+        (l * (1.0 - mix) + wl * mix, r * (1.0 - mix) + wr * mix)
+    }
+
+    fn box_clone(&self) -> Box<dyn Effect> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,212 @@
+pub mod utility;
+pub mod delay;
+pub mod reverb;
+pub mod modulation;
+pub mod distortion;
+pub mod eq;
+pub mod dynamics;
+pub mod formant;
+
+use std::fmt;
+
+use crate::effects::utility::*;
+use crate::effects::delay::*;
+use crate::effects::reverb::*;
+use crate::effects::modulation::*;
+use crate::effects::distortion::*;
+use crate::effects::eq::*;
+use crate::effects::dynamics::*;
+use crate::effects::formant::*;
+use crate::audio::BLOCK_SIZE;
+
+// Effects live on the master and bus chains as an ordered list of inserts.
+// The GUI builds the effect (any allocation happens there), boxes it up and
+// ships it over with AudioCommand::AddEffect, then addresses it by id.
+
+#[derive(Clone, Copy, Debug)]
+pub struct ParamDesc {
+    pub name: &'static str,
+    pub min: f32,
+    pub max: f32,
+    pub default: f32,
+}
+
+pub trait Effect: Send {
+    fn name(&self) -> &'static str;
+    fn params(&self) -> &'static [ParamDesc];
+    fn get_param(&self, idx: usize) -> f32;
+    fn set_param(&mut self, idx: usize, val: f32);
+
+    fn process(&mut self, l: f32, r: f32) -> (f32, f32);
+
+    // override if the effect can do better a block at a time
+    fn process_block(&mut self, buf: &mut [(f32, f32)]) {
+        for s in buf.iter_mut() {
+            *s = self.process(s.0, s.1);
+        }
+    }
+
+    // slot to key off instead of the effects own input, for sidechaining
+    fn sidechain(&self) -> Option<usize> { None }
+
+    // only called when sidechain() gives a slot, key is that slots block
+    fn process_block_keyed(&mut self, buf: &mut [(f32, f32)], _key: &[(f32, f32)]) {
+        self.process_block(buf);
+    }
+
+    // transport tempo, for anything that syncs to it
+    fn set_tempo(&mut self, _bpm: f32) {}
+
+    fn box_clone(&self) -> Box<dyn Effect>;
+}
+
+impl Clone for Box<dyn Effect> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+impl fmt::Debug for dyn Effect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// What the GUI can put in the rack
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EffectKind {
+    Utility,
+    Delay,
+    Reverb,
+    Chorus,
+    Flanger,
+    Phaser,
+    Distortion,
+    Bitcrusher,
+    Eq,
+    Compressor,
+    Formant,
+}
+
+impl EffectKind {
+    pub const ALL: [EffectKind; 11] = [
+        EffectKind::Utility,
+        EffectKind::Delay,
+        EffectKind::Reverb,
+        EffectKind::Chorus,
+        EffectKind::Flanger,
+        EffectKind::Phaser,
+        EffectKind::Distortion,
+        EffectKind::Bitcrusher,
+        EffectKind::Eq,
+        EffectKind::Compressor,
+        EffectKind::Formant,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EffectKind::Utility => "utility",
+            EffectKind::Delay => "delay",
+            EffectKind::Reverb => "reverb",
+            EffectKind::Chorus => "chorus",
+            EffectKind::Flanger => "flanger",
+            EffectKind::Phaser => "phaser",
+            EffectKind::Distortion => "distortion",
+            EffectKind::Bitcrusher => "bitcrusher",
+            EffectKind::Eq => "eq",
+            EffectKind::Compressor => "compressor",
+            EffectKind::Formant => "formant",
+        }
+    }
+
+    pub fn make(&self) -> Box<dyn Effect> {
+        match self {
+            EffectKind::Utility => Box::new(Utility::default()),
+            EffectKind::Delay => Box::new(Delay::default()),
+            EffectKind::Reverb => Box::new(Reverb::default()),
+            EffectKind::Chorus => Box::new(ModDelay::chorus()),
+            EffectKind::Flanger => Box::new(ModDelay::flanger()),
+            EffectKind::Phaser => Box::new(Phaser::default()),
+            EffectKind::Distortion => Box::new(Distortion::default()),
+            EffectKind::Bitcrusher => Box::new(Bitcrusher::default()),
+            EffectKind::Eq => Box::new(ParametricEq::default()),
+            EffectKind::Compressor => Box::new(Compressor::default()),
+            EffectKind::Formant => Box::new(Formant::default()),
+        }
+    }
+}
+
+pub struct Insert {
+    pub id: u64,
+    pub bypass: bool,
+    pub effect: Box<dyn Effect>,
+}
+
+// room for this many is made up front so adding never allocates on the audio thread
+pub const MAX_INSERTS: usize = 16;
+
+pub struct EffectChain {
+    pub inserts: Vec<Insert>,
+}
+
+impl Default for EffectChain {
+    fn default() -> Self {
+        EffectChain { inserts: Vec::with_capacity(MAX_INSERTS) }
+    }
+}
+
+impl EffectChain {
+    // a full chain hands the effect back
+    pub fn add(&mut self, id: u64, effect: Box<dyn Effect>) -> Option<Box<dyn Effect>> {
+        if self.inserts.len() >= MAX_INSERTS {
+            return Some(effect);
+        }
+        self.inserts.push(Insert { id, bypass: false, effect });
+        None
+    }
+
+    pub fn remove(&mut self, id: u64) -> Option<Box<dyn Effect>> {
+        let idx = self.inserts.iter().position(|x| x.id == id)?;
+        Some(self.inserts.remove(idx).effect)
+    }
+
+    pub fn set_bypass(&mut self, id: u64, bypass: bool) {
+        if let Some(insert) = self.inserts.iter_mut().find(|x| x.id == id) {
+            insert.bypass = bypass;
+        }
+    }
+
+    pub fn set_param(&mut self, id: u64, idx: usize, val: f32) {
+        if let Some(insert) = self.inserts.iter_mut().find(|x| x.id == id) {
+            insert.effect.set_param(idx, val);
+        }
+    }
+
+    pub fn set_tempo(&mut self, bpm: f32) {
+        for insert in self.inserts.iter_mut() {
+            insert.effect.set_tempo(bpm);
+        }
+    }
+
+    // moves the insert to position idx in the chain, clamped to the end
+    pub fn move_to(&mut self, id: u64, idx: usize) {
+        if let Some(from) = self.inserts.iter().position(|x| x.id == id) {
+            let insert = self.inserts.remove(from);
+            let idx = idx.min(self.inserts.len());
+            self.inserts.insert(idx, insert);
+        }
+    }
+
+    // keys are the pre fader slot blocks, for anything sidechained
+    pub fn process_block(&mut self, buf: &mut [(f32, f32)], keys: &[[(f32, f32); BLOCK_SIZE]]) {
+        for insert in self.inserts.iter_mut() {
+            if insert.bypass {
+                continue;
+            }
+            match insert.effect.sidechain().and_then(|k| keys.get(k)) {
+                Some(key) => insert.effect.process_block_keyed(buf, &key[..buf.len()]),
+                None => insert.effect.process_block(buf),
+            }
+        }
+    }
+}
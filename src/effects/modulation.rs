@@ -0,0 +1,238 @@
+use crate::effects::*;
+use crate::dsp::*;
+use crate::kmath::*;
+
+// Chorus, flanger and phaser. All three have the same knobs, the LFO for the
+// right side is offset by up to a quarter cycle by "stereo". with sync on the LFO
+// goes round once every "division" beats of the transport tempo instead of at "rate"
+
+const RATE: usize = 0;
+const DEPTH: usize = 1;
+const FEEDBACK: usize = 2;
+const STEREO: usize = 3;
+const MIX: usize = 4;
+const SYNC: usize = 5;
+const DIV: usize = 6;
+
+// lfo cycle lengths for sync, in beats
+const DIVISIONS: [f32; 7] = [0.5, 1.0, 2.0, 4.0, 8.0, 16.0, 32.0];
+
+static CHORUS_PARAMS: [ParamDesc; 7] = [
+    ParamDesc { name: "rate", min: 0.05, max: 5.0, default: 0.8 },
+    ParamDesc { name: "depth", min: 0.0, max: 1.0, default: 0.5 },
+    ParamDesc { name: "feedback", min: 0.0, max: 0.9, default: 0.0 },
+    ParamDesc { name: "stereo", min: 0.0, max: 1.0, default: 1.0 },
+    ParamDesc { name: "mix", min: 0.0, max: 1.0, default: 0.5 },
+    ParamDesc { name: "sync", min: 0.0, max: 1.0, default: 0.0 },
+    ParamDesc { name: "division", min: 0.0, max: 6.0, default: 3.0 },
+];
+
+static FLANGER_PARAMS: [ParamDesc; 7] = [
+    ParamDesc { name: "rate", min: 0.05, max: 5.0, default: 0.25 },
+    ParamDesc { name: "depth", min: 0.0, max: 1.0, default: 0.7 },
+    ParamDesc { name: "feedback", min: -0.95, max: 0.95, default: 0.6 },
+    ParamDesc { name: "stereo", min: 0.0, max: 1.0, default: 0.5 },
+    ParamDesc { name: "mix", min: 0.0, max: 1.0, default: 0.5 },
+    ParamDesc { name: "sync", min: 0.0, max: 1.0, default: 0.0 },
+    ParamDesc { name: "division", min: 0.0, max: 6.0, default: 3.0 },
+];
+
+static PHASER_PARAMS: [ParamDesc; 7] = [
+    ParamDesc { name: "rate", min: 0.05, max: 5.0, default: 0.4 },
+    ParamDesc { name: "depth", min: 0.0, max: 1.0, default: 0.7 },
+    ParamDesc { name: "feedback", min: -0.9, max: 0.9, default: 0.5 },
+    ParamDesc { name: "stereo", min: 0.0, max: 1.0, default: 0.5 },
+    ParamDesc { name: "mix", min: 0.0, max: 1.0, default: 0.5 },
+    ParamDesc { name: "sync", min: 0.0, max: 1.0, default: 0.0 },
+    ParamDesc { name: "division", min: 0.0, max: 6.0, default: 3.0 },
+];
+
+fn clamp_param(descs: &[ParamDesc], idx: usize, val: f32) -> f32 {
+    val.clamp(descs[idx].min, descs[idx].max)
+}
+
+fn lfo_hz(params: &[f32; 7], bpm: f32) -> f32 {
+    if params[SYNC] > 0.5 {
+        bpm / 60.0 / DIVISIONS[params[DIV].round() as usize]
+    } else {
+        params[RATE]
+    }
+}
+
+// chorus and flanger are the same thing with different delay ranges
+#[derive(Clone)]
+pub struct ModDelay {
+    flanger: bool,
+    params: [f32; 7],
+    bpm: f32,
+    lfo: Lfo,
+    lines: [DelayLine; 2],
+    fb: [f32; 2],
+}
+
+impl ModDelay {
+    fn new(flanger: bool) -> ModDelay {
+        let mut md = ModDelay {
+            flanger,
+            params: [0.0; 7],
+            bpm: 120.0,
+            lfo: Lfo::default(),
+            lines: [DelayLine::new(ms_to_samples(40.0) as usize), DelayLine::new(ms_to_samples(40.0) as usize)],
+            fb: [0.0; 2],
+        };
+        for (i, p) in md.params().iter().enumerate() {
+            md.params[i] = p.default;
+        }
+        md
+    }
+
+    pub fn chorus() -> ModDelay {
+        ModDelay::new(false)
+    }
+
+    pub fn flanger() -> ModDelay {
+        ModDelay::new(true)
+    }
+
+    // lfo -1..1 to delay in ms
+    fn delay_ms(&self, lfo: f32) -> f32 {
+        let depth = self.params[DEPTH];
+        if self.flanger {
+            0.5 + 4.0 * depth * 0.5 * (lfo + 1.0)
+        } else {
+            15.0 + 7.0 * depth * lfo
+        }
+    }
+}
+
+impl Effect for ModDelay {
+    fn name(&self) -> &'static str {
+        if self.flanger { "flanger" } else { "chorus" }
+    }
+
+    fn params(&self) -> &'static [ParamDesc] {
+        if self.flanger { &FLANGER_PARAMS } else { &CHORUS_PARAMS }
+    }
+
+    fn get_param(&self, idx: usize) -> f32 {
+        self.params.get(idx).copied().unwrap_or(0.0)
+    }
+
+    fn set_param(&mut self, idx: usize, val: f32) {
+        if idx >= self.params.len() { return; }
+        self.params[idx] = clamp_param(self.params(), idx, val);
+    }
+
+    fn process(&mut self, l: f32, r: f32) -> (f32, f32) {
+        self.lfo.tick(lfo_hz(&self.params, self.bpm));
+        let offsets = [0.0, 0.25 * self.params[STEREO]];
+        let input = [l, r];
+        let mut wet = [0.0; 2];
+        for side in 0..2 {
+            let lfo = if self.flanger { self.lfo.triangle(offsets[side]) } else { self.lfo.sine(offsets[side]) };
+            let d = ms_to_samples(self.delay_ms(lfo));
+            wet[side] = self.lines[side].tap_frac(d);
+            self.lines[side].push(input[side] + self.fb[side]);
+            self.fb[side] = wet[side] * self.params[FEEDBACK];
+        }
+
+        let mix = self.params[MIX];
+        (l * (1.0 - mix) + wet[0] * mix, r * (1.0 - mix) + wet[1] * mix)
+    }
+
+    fn set_tempo(&mut self, bpm: f32) {
+        self.bpm = bpm.max(1.0);
+    }
+
+    fn box_clone(&self) -> Box<dyn Effect> {
+        Box::new(self.clone())
+    }
+}
+
+const PHASER_STAGES: usize = 6;
+
+#[derive(Clone, Copy, Default)]
+struct AllpassStage {
+    x1: f32,
+    y1: f32,
+}
+
+impl AllpassStage {
+    fn tick(&mut self, x: f32, a: f32) -> f32 {
+        let y = a * x + self.x1 - a * self.y1;
+        self.x1 = x;
+        self.y1 = y;
+        y
+    }
+}
+
+#[derive(Clone)]
+pub struct Phaser {
+    params: [f32; 7],
+    bpm: f32,
+    lfo: Lfo,
+    stages: [[AllpassStage; PHASER_STAGES]; 2],
+    fb: [f32; 2],
+}
+
+impl Default for Phaser {
+    fn default() -> Self {
+        let mut p = Phaser {
+            params: [0.0; 7],
+            bpm: 120.0,
+            lfo: Lfo::default(),
+            stages: [[AllpassStage::default(); PHASER_STAGES]; 2],
+            fb: [0.0; 2],
+        };
+        for (i, d) in PHASER_PARAMS.iter().enumerate() {
+            p.params[i] = d.default;
+        }
+        p
+    }
+}
+
+impl Effect for Phaser {
+    fn name(&self) -> &'static str { "phaser" }
+    fn params(&self) -> &'static [ParamDesc] { &PHASER_PARAMS }
+
+    fn get_param(&self, idx: usize) -> f32 {
+        self.params.get(idx).copied().unwrap_or(0.0)
+    }
+
+    fn set_param(&mut self, idx: usize, val: f32) {
+        if idx >= self.params.len() { return; }
+        self.params[idx] = clamp_param(&PHASER_PARAMS, idx, val);
+    }
+
+    fn process(&mut self, l: f32, r: f32) -> (f32, f32) {
+        self.lfo.tick(lfo_hz(&self.params, self.bpm));
+        let offsets = [0.0, 0.25 * self.params[STEREO]];
+        let input = [l, r];
+        let mut wet = [0.0; 2];
+        for side in 0..2 {
+            // sweep the notches exponentially from 200hz up to 5 octaves above
+            let t = 0.5 * (self.lfo.sine(offsets[side]) + 1.0);
+            let f = 200.0 * 2.0f32.powf(5.0 * self.params[DEPTH] * t);
+            let k = (PI * f / SAMPLE_RATE).tan();
+            let a = (k - 1.0) / (k + 1.0);
+
+            let mut x = input[side] + self.fb[side];
+            for stage in self.stages[side].iter_mut() {
+                x = stage.tick(x, a);
+            }
+            wet[side] = x;
+            self.fb[side] = x * self.params[FEEDBACK];
+        }
+
+        let mix = self.params[MIX];
+        (l * (1.0 - mix) + wet[0] * mix, r * (1.0 - mix) + wet[1] * mix)
+    }
+
+    fn set_tempo(&mut self, bpm: f32) {
+        self.bpm = bpm.max(1.0);
+    }
+
+    fn box_clone(&self) -> Box<dyn Effect> {
+        Box::new(self.clone())
+    }
+}
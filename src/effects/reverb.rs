@@ -0,0 +1,149 @@
+use crate::effects::*;
+use crate::dsp::*;
+
+// Freeverb: 8 parallel damped combs into 4 series allpasses, per side.
+// Right side is the same but slightly longer which is where the stereo comes from.
+
+const COMB_TUNING: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNING: [usize; 4] = [556, 441, 341, 225];
+const STEREO_SPREAD: usize = 23;
+const MAX_SIZE: f32 = 1.5;
+const MAX_PREDELAY_MS: f32 = 200.0;
+
+// freeverb's fixed gains
+const INPUT_GAIN: f32 = 0.015;
+const WET_GAIN: f32 = 3.0;
+
+const SIZE: usize = 0;
+const DECAY: usize = 1;
+const DAMPING: usize = 2;
+const PREDELAY: usize = 3;
+const WIDTH: usize = 4;
+const MIX: usize = 5;
+
+static PARAMS: [ParamDesc; 6] = [
+    ParamDesc { name: "size", min: 0.3, max: MAX_SIZE, default: 1.0 },
+    ParamDesc { name: "decay", min: 0.0, max: 1.0, default: 0.6 },
+    ParamDesc { name: "damping", min: 0.0, max: 1.0, default: 0.5 },
+    ParamDesc { name: "predelay", min: 0.0, max: MAX_PREDELAY_MS, default: 10.0 },
+    ParamDesc { name: "width", min: 0.0, max: 1.0, default: 1.0 },
+    ParamDesc { name: "mix", min: 0.0, max: 1.0, default: 0.25 },
+];
+
+#[derive(Clone)]
+struct Comb {
+    line: DelayLine,
+    tuning: usize,
+    store: f32,
+}
+
+impl Comb {
+    fn tick(&mut self, x: f32, size: f32, feedback: f32, damp: f32) -> f32 {
+        let out = self.line.tap((self.tuning as f32 * size) as usize - 1);
+        self.store = out * (1.0 - damp) + self.store * damp;
+        self.line.push(x + self.store * feedback);
+        out
+    }
+}
+
+#[derive(Clone)]
+struct Allpass {
+    line: DelayLine,
+    tuning: usize,
+}
+
+impl Allpass {
+    fn tick(&mut self, x: f32, size: f32) -> f32 {
+        let buf_out = self.line.tap((self.tuning as f32 * size) as usize - 1);
+        self.line.push(x + buf_out * 0.5);
+        buf_out - x
+    }
+}
+
+#[derive(Clone)]
+pub struct Reverb {
+    params: [f32; 6],
+
+    predelay: DelayLine,
+    combs: [Vec<Comb>; 2],
+    allpasses: [Vec<Allpass>; 2],
+}
+
+impl Default for Reverb {
+    fn default() -> Self {
+        let side = |spread: usize| {
+            let combs = COMB_TUNING.iter().map(|&t| Comb {
+                line: DelayLine::new(((t + spread) as f32 * MAX_SIZE) as usize + 1),
+                tuning: t + spread,
+                store: 0.0,
+            }).collect::<Vec<_>>();
+            let allpasses = ALLPASS_TUNING.iter().map(|&t| Allpass {
+                line: DelayLine::new(((t + spread) as f32 * MAX_SIZE) as usize + 1),
+                tuning: t + spread,
+            }).collect::<Vec<_>>();
+            (combs, allpasses)
+        };
+        let (cl, al) = side(0);
+        let (cr, ar) = side(STEREO_SPREAD);
+
+        let mut rev = Reverb {
+            params: [0.0; 6],
+            predelay: DelayLine::new(ms_to_samples(MAX_PREDELAY_MS) as usize + 2),
+            combs: [cl, cr],
+            allpasses: [al, ar],
+        };
+        for (i, p) in PARAMS.iter().enumerate() {
+            rev.set_param(i, p.default);
+        }
+        rev
+    }
+}
+
+impl Effect for Reverb {
+    fn name(&self) -> &'static str { "reverb" }
+    fn params(&self) -> &'static [ParamDesc] { &PARAMS }
+
+    fn get_param(&self, idx: usize) -> f32 {
+        self.params.get(idx).copied().unwrap_or(0.0)
+    }
+
+    fn set_param(&mut self, idx: usize, val: f32) {
+        if idx >= PARAMS.len() { return; }
+        self.params[idx] = val.clamp(PARAMS[idx].min, PARAMS[idx].max);
+    }
+
+    fn process(&mut self, l: f32, r: f32) -> (f32, f32) {
+        let size = self.params[SIZE];
+        // freeverb's room size range
+        let feedback = 0.7 + 0.28 * self.params[DECAY];
+        let damp = 0.4 * self.params[DAMPING];
+
+        self.predelay.push((l + r) * INPUT_GAIN);
+        let x = self.predelay.tap(ms_to_samples(self.params[PREDELAY]) as usize);
+
+        let mut wet = [0.0; 2];
+        for ((w, combs), allpasses) in wet.iter_mut().zip(self.combs.iter_mut()).zip(self.allpasses.iter_mut()) {
+            let mut acc = 0.0;
+            for comb in combs.iter_mut() {
+                acc += comb.tick(x, size, feedback, damp);
+            }
+            for ap in allpasses.iter_mut() {
+                acc = ap.tick(acc, size);
+            }
+            *w = acc * WET_GAIN;
+        }
+
+        let width = self.params[WIDTH];
+        let wet1 = 0.5 + 0.5 * width;
+        let wet2 = 0.5 - 0.5 * width;
+        let wet_l = wet[0] * wet1 + wet[1] * wet2;
+        let wet_r = wet[1] * wet1 + wet[0] * wet2;
+
+        let mix = self.params[MIX];
+        (l * (1.0 - mix) + wet_l * mix, r * (1.0 - mix) + wet_r * mix)
+    }
+
+    fn box_clone(&self) -> Box<dyn Effect> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,66 @@
+use crate::effects::*;
+use crate::audio::*;
+
+// Gain, pan and stereo width. Mostly here so the chain has something in it
+static PARAMS: [ParamDesc; 3] = [
+    ParamDesc { name: "gain", min: -60.0, max: 24.0, default: 0.0 },
+    ParamDesc { name: "pan", min: -1.0, max: 1.0, default: 0.0 },
+    ParamDesc { name: "width", min: 0.0, max: 2.0, default: 1.0 },
+];
+
+#[derive(Clone)]
+pub struct Utility {
+    pub gain: f32,
+    pub pan: f32,
+    pub width: f32,
+}
+
+impl Default for Utility {
+    fn default() -> Self {
+        Utility {
+            gain: PARAMS[0].default,
+            pan: PARAMS[1].default,
+            width: PARAMS[2].default,
+        }
+    }
+}
+
+impl Effect for Utility {
+    fn name(&self) -> &'static str { "utility" }
+    fn params(&self) -> &'static [ParamDesc] { &PARAMS }
+
+    fn get_param(&self, idx: usize) -> f32 {
+        match idx {
+            0 => self.gain,
+            1 => self.pan,
+            2 => self.width,
+            _ => 0.0,
+        }
+    }
+
+    fn set_param(&mut self, idx: usize, val: f32) {
+        match idx {
+            0 => self.gain = val,
+            1 => self.pan = val,
+            2 => self.width = val,
+            _ => {},
+        }
+    }
+
+    fn process(&mut self, l: f32, r: f32) -> (f32, f32) {
+        let g = db_to_vol(self.gain);
+
+        // mid side for width
+        let m = (l + r) * 0.5;
+        let s = (l - r) * 0.5 * self.width;
+        let (l, r) = (m + s, m - s);
+
+        let (gl, gr) = pan_gains(self.pan);
+
+        (l * g * gl, r * g * gr)
+    }
+
+    fn box_clone(&self) -> Box<dyn Effect> {
+        Box::new(self.clone())
+    }
+}
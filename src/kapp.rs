@@ -6,6 +6,7 @@ use crate::texture_buffer::*;
 use crate::kmath::*;
 use crate::video::*;
 use crate::audio::*;
+use crate::effects::Effect;
 
 use cpal::Stream;
 use cpal::traits::*;
@@ -13,7 +14,10 @@ use glutin::dpi::LogicalPosition;
 use glutin::window::CursorIcon;
 use ringbuf::*;
 
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{SystemTime, Instant, Duration};
 
 pub use glutin::event::VirtualKeyCode;
@@ -50,10 +54,12 @@ pub struct FrameInputState {
     pub rmb: KeyStatus,
     pub mmb: KeyStatus,
     pub scroll_delta: f32,
+    pub dropped_files: Vec<PathBuf>,
     pub t: f32,
     pub dt: f32,
     pub frame: u32,
     pub seed: u32,
+    pub clock: u64,     // audio threads sample count, for At commands
 }
 
 impl FrameInputState {
@@ -75,6 +81,7 @@ impl FrameInputState {
             mouse_pos: Vec2::new(0.0, 0.0), 
             mouse_delta: Vec2::new(0.0, 0.0), 
             scroll_delta: 0.0,
+            dropped_files: Vec::new(),
             curr_keys: HashSet::new(),
             prev_keys: HashSet::new(),
             repeat_keys: HashSet::new(),
@@ -84,6 +91,7 @@ impl FrameInputState {
             t: 0.0,
             dt: 0.0,
             frame: 0,
+            clock: 0,
             seed: SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or(Duration::from_nanos(34123123)).subsec_nanos(),
         }
     }
@@ -111,14 +119,47 @@ impl FrameOutputs {
             set_cursor: None,
         }
     }
+
+    // fire and forget, the voice releases itself after dur seconds
+    pub fn play_for(&mut self, slot: usize, sd: SoundDesc, dur: f32) -> VoiceHandle {
+        let h = VoiceHandle::next();
+        self.sounds.push(AudioCommand::PlayFor(h.0, slot, Box::new(sd), dur));
+        h
+    }
+
+    // held until released or stopped
+    pub fn play(&mut self, slot: usize, sd: SoundDesc) -> VoiceHandle {
+        let h = VoiceHandle::next();
+        self.sounds.push(AudioCommand::PlayHold(h.0, slot, Box::new(sd)));
+        h
+    }
+
+    pub fn release(&mut self, h: VoiceHandle) {
+        self.sounds.push(AudioCommand::Release(h.0));
+    }
+
+    // sfx voices run their own pitch sweep and dont follow this
+    pub fn retune(&mut self, h: VoiceHandle, f: f32) {
+        self.sounds.push(AudioCommand::Retune(h.0, f));
+    }
+
+    pub fn stop(&mut self, h: VoiceHandle) {
+        self.sounds.push(AudioCommand::Stop(h.0));
+    }
 }
 
+// sequencer steps, clicks and knob tweaks all go through here so give it some room
+const COMMAND_RING: usize = 4096;
+
 pub struct Application {
     video: Video,
     root_scene: SynthGUI,
 
     audio_stream: Stream,
     channel: Producer<AudioCommand>,
+    clock: Arc<AtomicU64>,
+    trash: Consumer<Box<dyn Effect>>,
+    unsent: VecDeque<AudioCommand>, // whatever didnt fit in the ring last frame
 
     t_last: Instant,
     instant_mouse_pos: Vec2,
@@ -135,8 +176,10 @@ impl Application {
     
         let video = Video::new("ksynth2", xres as f32, yres as f32, event_loop);
 
-        let rb = RingBuffer::<AudioCommand>::new(64);
+        let rb = RingBuffer::<AudioCommand>::new(COMMAND_RING);
         let (mut prod, mut cons) = rb.split();
+        let clock = Arc::new(AtomicU64::new(0));
+        let (trash_prod, trash) = RingBuffer::<Box<dyn Effect>>::new(64).split();
         
         let app = Application {
             video,
@@ -145,8 +188,11 @@ impl Application {
             old_mouse_pos: LogicalPosition { x: 0.0, y: 0.0 },
             instant_mouse_pos: Vec2::zero(),
             current: FrameInputState::new(xres as f32 / yres as f32),           
-            audio_stream: stream_setup_for(sample_next, cons).expect("no can make stream"),
+            audio_stream: stream_setup_for(block_next, cons, clock.clone(), trash_prod).expect("no can make stream"),
             channel: prod,
+            clock,
+            trash,
+            unsent: VecDeque::new(),
             plant_cursor: false,
         };
         app.audio_stream.play().expect("no can play stream");
@@ -218,6 +264,10 @@ impl Application {
                 },
 
 
+                DroppedFile(path) => {
+                    self.current.dropped_files.push(path);
+                },
+
                 // Mouse motion
                 // maybe we actually need mouse device events or something
                 CursorMoved {
@@ -249,6 +299,9 @@ impl Application {
                 self.current.t += dt;
                 self.t_last = t_now;
                 self.current.frame += 1;
+                self.current.clock = self.clock.load(Ordering::Relaxed);
+                // effects the audio thread is done with get freed here
+                while self.trash.pop().is_some() {}
                 self.current.mouse_delta = self.instant_mouse_pos - self.current.mouse_pos;
                 self.current.mouse_pos = self.instant_mouse_pos;
                 let state = self.current.clone();
@@ -256,6 +309,7 @@ impl Application {
                 self.current.repeat_keys = HashSet::new();
                 self.current.seed = khash(self.current.seed * 196513497);
                 self.current.scroll_delta = 0.0;
+                self.current.dropped_files.clear();
                 self.current.lmb = match self.current.lmb {KeyStatus::JustPressed | KeyStatus::Pressed => KeyStatus::Pressed, KeyStatus::JustReleased | KeyStatus::Released => KeyStatus::Released};
                 self.current.mmb = match self.current.mmb {KeyStatus::JustPressed | KeyStatus::Pressed => KeyStatus::Pressed, KeyStatus::JustReleased | KeyStatus::Released => KeyStatus::Released};
                 self.current.rmb = match self.current.rmb {KeyStatus::JustPressed | KeyStatus::Pressed => KeyStatus::Pressed, KeyStatus::JustReleased | KeyStatus::Released => KeyStatus::Released};
@@ -280,8 +334,13 @@ impl Application {
                     }
                 }
 
-                for sc in new_outputs.sounds.iter() {
-                    self.channel.push(*sc).ok().unwrap();
+                // if the ring is full hang on to the rest and try again next frame, in order
+                self.unsent.extend(new_outputs.sounds.drain(..));
+                while let Some(sc) = self.unsent.pop_front() {
+                    if let Err(sc) = self.channel.push(sc) {
+                        self.unsent.push_front(sc);
+                        break;
+                    }
                 }
                 self.video.render(&new_outputs, state.screen_rect.aspect());
             },
@@ -308,8 +367,8 @@ impl Application {
 
 
 
-fn sample_next(o: &mut SampleRequestOptions) -> f32 {
-    o.mixer.tick()
+fn block_next(o: &mut SampleRequestOptions, buf: &mut [(f32, f32)]) {
+    o.mixer.render(buf)
 }
 
 pub struct SampleRequestOptions {
@@ -323,16 +382,16 @@ pub struct SampleRequestOptions {
     pub channel: Consumer<AudioCommand>,
 }
 
-pub fn stream_setup_for<F>(on_sample: F, channel: Consumer<AudioCommand>) -> Result<cpal::Stream, anyhow::Error>
+pub fn stream_setup_for<F>(on_block: F, channel: Consumer<AudioCommand>, clock: Arc<AtomicU64>, trash: Producer<Box<dyn Effect>>) -> Result<cpal::Stream, anyhow::Error>
 where
-    F: FnMut(&mut SampleRequestOptions) -> f32 + std::marker::Send + 'static + Copy,
+    F: FnMut(&mut SampleRequestOptions, &mut [(f32, f32)]) + std::marker::Send + 'static + Copy,
 {
     let (_host, device, config) = host_device_setup()?;
 
     match config.sample_format() {
-        cpal::SampleFormat::F32 => stream_make::<f32, _>(&device, &config.into(), on_sample, channel),
-        cpal::SampleFormat::I16 => stream_make::<i16, _>(&device, &config.into(), on_sample, channel),
-        cpal::SampleFormat::U16 => stream_make::<u16, _>(&device, &config.into(), on_sample, channel),
+        cpal::SampleFormat::F32 => stream_make::<f32, _>(&device, &config.into(), on_block, channel, clock, trash),
+        cpal::SampleFormat::I16 => stream_make::<i16, _>(&device, &config.into(), on_block, channel, clock, trash),
+        cpal::SampleFormat::U16 => stream_make::<u16, _>(&device, &config.into(), on_block, channel, clock, trash),
     }
 }
 
@@ -354,20 +413,25 @@ pub fn host_device_setup(
 pub fn stream_make<T, F>(
     device: &cpal::Device,
     config: &cpal::StreamConfig,
-    on_sample: F,
+    on_block: F,
     channel: Consumer<AudioCommand>,
+    clock: Arc<AtomicU64>,
+    trash: Producer<Box<dyn Effect>>,
 ) -> Result<cpal::Stream, anyhow::Error>
 where
     T: cpal::Sample,
-    F: FnMut(&mut SampleRequestOptions) -> f32 + std::marker::Send + 'static + Copy,
+    F: FnMut(&mut SampleRequestOptions, &mut [(f32, f32)]) + std::marker::Send + 'static + Copy,
 {
     let sample_rate = config.sample_rate.0 as f32;
     let nchannels = config.channels as usize;
+    let mut mixer = Mixer::default();
+    mixer.clock = clock;
+    mixer.trash = Some(trash);
     let mut request = SampleRequestOptions {
         sample_rate,
         nchannels,
 
-        mixer: Mixer::default(),
+        mixer,
 
         channel,
     };
@@ -376,7 +440,7 @@ where
     let stream = device.build_output_stream(
         config,
         move |output: &mut [T], _: &cpal::OutputCallbackInfo| {
-            on_window(output, &mut request, on_sample)
+            on_window(output, &mut request, on_block)
         },
         err_fn,
     )?;
@@ -384,18 +448,32 @@ where
     Ok(stream)
 }
 
-fn on_window<T, F>(output: &mut [T], request: &mut SampleRequestOptions, mut on_sample: F)
+fn on_window<T, F>(output: &mut [T], request: &mut SampleRequestOptions, mut on_block: F)
 where
     T: cpal::Sample,
-    F: FnMut(&mut SampleRequestOptions) -> f32 + std::marker::Send + 'static,
+    F: FnMut(&mut SampleRequestOptions, &mut [(f32, f32)]) + std::marker::Send + 'static,
 {
-    if let Some(sc) = request.channel.pop() {
+    // one frame can send a bunch of commands, eg adding an effect and setting it up
+    while let Some(sc) = request.channel.pop() {
         request.mixer.handle_command(sc);
     }
-    for frame in output.chunks_mut(request.nchannels) {
-        let value: T = cpal::Sample::from::<f32>(&on_sample(request));
-        for sample in frame.iter_mut() {
-            *sample = value;
+    let mut block = [(0.0f32, 0.0f32); BLOCK_SIZE];
+    for chunk in output.chunks_mut(request.nchannels * BLOCK_SIZE) {
+        let nframes = chunk.len() / request.nchannels;
+        on_block(request, &mut block[..nframes]);
+        for (frame, &(l, r)) in chunk.chunks_mut(request.nchannels).zip(block.iter()) {
+            if frame.len() == 1 {
+                frame[0] = cpal::Sample::from::<f32>(&((l + r) * 0.5));
+                continue;
+            }
+            for (i, sample) in frame.iter_mut().enumerate() {
+                let value = match i {
+                    0 => l,
+                    1 => r,
+                    _ => (l + r) * 0.5,
+                };
+                *sample = cpal::Sample::from::<f32>(&value);
+            }
         }
     }
 }
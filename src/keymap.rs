@@ -0,0 +1,124 @@
+use crate::kapp::VirtualKeyCode;
+use crate::kapp::VirtualKeyCode::*;
+
+use std::collections::HashMap;
+use std::path::Path;
+
+// Computer keyboard to note layouts. A layout maps keys to semitones above its
+// lowest key, KeyShift turns that into a MIDI note.
+
+// keys the app keeps for itself, no layout gets them
+pub const PAD_KEYS: [VirtualKeyCode; 5] = [F1, F2, F3, F4, F5];
+pub const OCTAVE_UP: VirtualKeyCode = Up;
+pub const OCTAVE_DOWN: VirtualKeyCode = Down;
+pub const NEXT_LAYOUT: VirtualKeyCode = F9;
+pub const PLAY_KEY: VirtualKeyCode = Space;
+
+pub fn is_shortcut(k: VirtualKeyCode) -> bool {
+    PAD_KEYS.contains(&k) || [OCTAVE_UP, OCTAVE_DOWN, NEXT_LAYOUT, PLAY_KEY].contains(&k)
+}
+
+// what keymap files can call the keys
+const KEY_NAMES: [(&str, VirtualKeyCode); 47] = [
+    ("a", A), ("b", B), ("c", C), ("d", D), ("e", E), ("f", F), ("g", G), ("h", H), ("i", I),
+    ("j", J), ("k", K), ("l", L), ("m", M), ("n", N), ("o", O), ("p", P), ("q", Q), ("r", R),
+    ("s", S), ("t", T), ("u", U), ("v", V), ("w", W), ("x", X), ("y", Y), ("z", Z),
+    ("1", Key1), ("2", Key2), ("3", Key3), ("4", Key4), ("5", Key5),
+    ("6", Key6), ("7", Key7), ("8", Key8), ("9", Key9), ("0", Key0),
+    ("minus", Minus), ("equals", Equals), ("lbracket", LBracket), ("rbracket", RBracket),
+    ("backslash", Backslash), ("semicolon", Semicolon), ("apostrophe", Apostrophe),
+    ("comma", Comma), ("period", Period), ("slash", Slash), ("grave", Grave),
+];
+
+pub fn key_by_name(name: &str) -> Option<VirtualKeyCode> {
+    KEY_NAMES.iter().find(|(n, _)| *n == name).map(|(_, k)| *k)
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyLayout {
+    pub name: String,
+    pub keys: HashMap<VirtualKeyCode, usize>,
+}
+
+impl KeyLayout {
+    fn from_pairs(name: &str, pairs: &[(VirtualKeyCode, usize)]) -> KeyLayout {
+        KeyLayout {
+            name: name.to_string(),
+            keys: pairs.iter().filter(|(k, _)| !is_shortcut(*k)).copied().collect(),
+        }
+    }
+
+    // two rows, each an octave and a bit, black keys on the row above
+    pub fn tracker() -> KeyLayout {
+        KeyLayout::from_pairs("tracker", &[
+            (Z, 0), (S, 1), (X, 2), (D, 3), (C, 4), (V, 5), (G, 6), (B, 7), (H, 8), (N, 9), (J, 10), (M, 11),
+            (Comma, 12), (L, 13), (Period, 14), (Semicolon, 15), (Slash, 16),
+            (Q, 12), (Key2, 13), (W, 14), (Key3, 15), (E, 16), (R, 17), (Key5, 18), (T, 19), (Key6, 20), (Y, 21), (Key7, 22), (U, 23),
+            (I, 24), (Key9, 25), (O, 26), (Key0, 27), (P, 28), (LBracket, 29), (Equals, 30), (RBracket, 31),
+        ])
+    }
+
+    // one row of white keys on the home row
+    pub fn piano() -> KeyLayout {
+        KeyLayout::from_pairs("piano", &[
+            (A, 0), (W, 1), (S, 2), (E, 3), (D, 4), (F, 5), (T, 6), (G, 7), (Y, 8), (H, 9), (U, 10), (J, 11),
+            (K, 12), (O, 13), (L, 14), (P, 15), (Semicolon, 16), (Apostrophe, 17),
+        ])
+    }
+
+    // semitones going across, fourths going up, same shape in every key
+    pub fn isomorphic() -> KeyLayout {
+        let rows: [&[VirtualKeyCode]; 4] = [
+            &[Z, X, C, V, B, N, M, Comma, Period, Slash],
+            &[A, S, D, F, G, H, J, K, L, Semicolon, Apostrophe],
+            &[Q, W, E, R, T, Y, U, I, O, P, LBracket, RBracket],
+            &[Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0, Minus, Equals],
+        ];
+        let pairs: Vec<_> = rows.iter().enumerate()
+            .flat_map(|(row, keys)| keys.iter().enumerate().map(move |(col, k)| (*k, row * 5 + col)))
+            .collect();
+        KeyLayout::from_pairs("isomorphic", &pairs)
+    }
+
+    pub fn builtin() -> Vec<KeyLayout> {
+        vec![KeyLayout::tracker(), KeyLayout::piano(), KeyLayout::isomorphic()]
+    }
+
+    // "name <layout name>" then "<key> <semitones>" lines, # for comments
+    pub fn parse(text: &str) -> Result<KeyLayout, String> {
+        let mut name = "custom".to_string();
+        let mut pairs = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(n) = line.strip_prefix("name ") {
+                name = n.trim().to_string();
+                continue;
+            }
+            let mut it = line.split_whitespace();
+            let (k, n) = (it.next().unwrap(), it.next());
+            let key = key_by_name(&k.to_lowercase()).ok_or(format!("line {}: no key called {}", i + 1, k))?;
+            let semis = n.and_then(|n| n.parse::<usize>().ok()).ok_or(format!("line {}: bad note for {}", i + 1, k))?;
+            pairs.push((key, semis));
+        }
+        if pairs.is_empty() {
+            return Err("no keys in layout".to_string());
+        }
+        Ok(KeyLayout::from_pairs(&name, &pairs))
+    }
+
+    pub fn load(path: &Path) -> Result<KeyLayout, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        KeyLayout::parse(&text)
+    }
+
+    pub fn note(&self, k: VirtualKeyCode) -> Option<usize> {
+        self.keys.get(&k).copied()
+    }
+
+    pub fn highest(&self) -> usize {
+        self.keys.values().copied().max().unwrap_or(0)
+    }
+}
@@ -2,12 +2,24 @@ mod kmath;
 mod kimg;
 mod video;
 mod audio;
+mod effects;
+mod dsp;
 mod texture_buffer;
 mod renderers;
 mod kapp;
 mod synth_gui;
 mod priority_queue;
 mod widgets;
+mod voices;
+mod wav;
+mod patch;
+mod tuning;
+mod note;
+mod keymap;
+mod arp;
+mod seq;
+mod transport;
+mod smf;
 
 use crate::kapp::*;
 
@@ -0,0 +1,50 @@
+// Notes are MIDI numbers, 60 is C4 and 69 is A4. Pitch goes through the tuning,
+// this is just the naming and the 12-TET maths everything else leans on.
+
+pub const NOTE_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+pub const A4: i32 = 69;
+pub const DEFAULT_A4_FREQ: f32 = 440.0;
+
+// lowest key on the computer keyboard before octave shift and transpose
+pub const KEY_BASE: i32 = 48;
+
+pub fn note_name(midi: i32) -> String {
+    format!("{}{}", NOTE_NAMES[midi.rem_euclid(12) as usize], midi.div_euclid(12) - 1)
+}
+
+pub fn pitch_class(midi: i32) -> usize {
+    midi.rem_euclid(12) as usize
+}
+
+pub fn octave(midi: i32) -> i32 {
+    midi.div_euclid(12) - 1
+}
+
+// fractional notes are fine, for roots and bends
+pub fn midi_to_freq(midi: f32, a4: f32) -> f32 {
+    a4 * 2.0f32.powf((midi - A4 as f32) / 12.0)
+}
+
+// what the keyboard plays right now
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeyShift {
+    pub octave: i32,
+    pub transpose: i32,
+}
+
+impl KeyShift {
+    pub const MAX_OCTAVE: i32 = 4;
+
+    pub fn key_to_midi(&self, key: usize) -> i32 {
+        (KEY_BASE + key as i32 + self.transpose + 12 * self.octave).clamp(0, 127)
+    }
+
+    pub fn octave_up(&mut self) {
+        self.octave = (self.octave + 1).min(Self::MAX_OCTAVE);
+    }
+
+    pub fn octave_down(&mut self) {
+        self.octave = (self.octave - 1).max(-Self::MAX_OCTAVE);
+    }
+}
@@ -0,0 +1,98 @@
+use std::path::Path;
+
+use crate::voices::morph::*;
+
+// Patches on disk are plain text, one "name value" per line so theyre easy to diff and hand edit.
+// Morph keyframes get a line each: time curve then the frame fields in order.
+// Anything unrecognised is skipped so old patches still load.
+
+#[derive(Default, Clone)]
+pub struct Patch {
+    pub values: Vec<(String, f32)>,
+    pub morph: Option<MorphDesc>,
+}
+
+fn frame_to_text(key: &Keyframe) -> String {
+    let mut s = format!("{} {}", key.time, key.curve);
+    for i in 0..FRAME_FIELDS.len() {
+        s += &format!(" {}", key.frame.get(i));
+    }
+    s
+}
+
+fn frame_from_words(words: &[&str]) -> Option<Keyframe> {
+    let nums: Vec<f32> = words.iter().map(|w| w.parse().ok()).collect::<Option<_>>()?;
+    if nums.len() != 2 + FRAME_FIELDS.len() {
+        return None;
+    }
+    let mut key = Keyframe { time: nums[0], curve: nums[1], frame: TimbreFrame::default() };
+    for i in 0..FRAME_FIELDS.len() {
+        key.frame.set(i, nums[2 + i]);
+    }
+    Some(key)
+}
+
+impl Patch {
+    pub fn get(&self, name: &str) -> Option<f32> {
+        self.values.iter().find(|(n, _)| n == name).map(|(_, v)| *v)
+    }
+
+    pub fn set(&mut self, name: &str, v: f32) {
+        match self.values.iter_mut().find(|(n, _)| n == name) {
+            Some(x) => x.1 = v,
+            None => self.values.push((name.to_string(), v)),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut s = String::new();
+        for (name, val) in self.values.iter() {
+            s += &format!("{} {}\n", name, val);
+        }
+        if let Some(morph) = &self.morph {
+            for key in morph.keys() {
+                s += &format!("morph_key {}\n", frame_to_text(key));
+            }
+            s += &format!("morph_release {}\n", frame_to_text(&morph.release));
+        }
+        s
+    }
+
+    pub fn from_text(text: &str) -> Patch {
+        let mut patch = Patch::default();
+        let mut morph = MorphDesc { len: 0, ..MorphDesc::default() };
+        let mut has_morph = false;
+        for line in text.lines() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                ["morph_key", rest @ ..] => if let Some(key) = frame_from_words(rest) {
+                    has_morph = true;
+                    morph.insert(key);
+                },
+                ["morph_release", rest @ ..] => if let Some(key) = frame_from_words(rest) {
+                    morph.release = key;
+                },
+                [name, val] => if let Ok(v) = val.parse() {
+                    patch.values.push((name.to_string(), v));
+                },
+                _ => {},
+            }
+        }
+        if has_morph {
+            patch.morph = Some(morph);
+        }
+        patch
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        std::fs::write(path, self.to_text()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn load(path: &Path) -> Result<Patch, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(Patch::from_text(&text))
+    }
+}
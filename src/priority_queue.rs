@@ -7,6 +7,26 @@ impl <K: Ord + Clone,V: Clone> PriorityQueue<K, V> {
         PriorityQueue { heap: Vec::new() }
     }
 
+    pub fn with_capacity(n: usize) -> PriorityQueue<K, V> {
+        PriorityQueue { heap: Vec::with_capacity(n) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn peek_key(&self) -> Option<&K> {
+        self.heap.first().map(|x| &x.0)
+    }
+
+    // keeps whatever f says to, f sees each one once in no particular order
+    pub fn retain<F: FnMut(&K, &V) -> bool>(&mut self, mut f: F) {
+        self.heap.retain(|(k, v)| f(k, v));
+        for i in (0..self.heap.len() / 2).rev() {
+            self.downheap(i);
+        }
+    }
+
     pub fn push(&mut self, k: K, v: V) {
         self.heap.push((k, v));
         self.upheap(self.heap.len() - 1);
@@ -16,9 +36,7 @@ impl <K: Ord + Clone,V: Clone> PriorityQueue<K, V> {
         if self.heap.len() == 0 {
             return None;
         }
-        let return_val = self.heap[0].clone();
-        self.heap[0] = self.heap[self.heap.len() - 1].clone();
-        self.heap.truncate(self.heap.len() - 1);
+        let return_val = self.heap.swap_remove(0);
         self.downheap(0);
         Some(return_val)
     }
@@ -57,14 +75,16 @@ impl <K: Ord + Clone,V: Clone> PriorityQueue<K, V> {
         }
     }
     
+    // smallest key on top, same as downheap
     fn upheap(&mut self, mut idx: usize) {
-        let parent = idx / 2;
-        while self.heap[parent].0 < self.heap[idx].0 {
-            self.heap.swap(idx, parent);
-            if parent == 0 {
+        while idx > 0 {
+            let parent = (idx - 1) / 2;
+            if self.heap[idx].0 < self.heap[parent].0 {
+                self.heap.swap(idx, parent);
+                idx = parent;
+            } else {
                 break;
             }
-            idx = parent;
         }
     }
 }
\ No newline at end of file
@@ -6,6 +6,10 @@ pub fn glyph_buffer_to_canvas(buf: &GlyphBuffer, a: f32) -> CTCanvas {
         if c >= 'a' as u8 && c <= 'z' as u8 {
             c -= 'a' as u8 - 'A' as u8;
         }
+        // no # in the font, sharps get drawn as ^
+        if c == b'#' {
+            c = b'^';
+        }
         if c >= '-' as u8 && c <= '_' as u8 {
             let x = c - '-' as u8;
             let w = '_' as u8 - '-' as u8 + 1; // maybe +1
@@ -0,0 +1,130 @@
+use crate::kmath::*;
+use crate::patch::*;
+use crate::transport::Tick;
+
+// Step sequencer. Patterns of up to 32 steps, played one after another off the chain.
+// Like the arp it only works out when notes happen on the audio clock, the GUI turns
+// them into sounds. Parameter locks are a patch of knob values laid over one slots own.
+
+pub const MAX_STEPS: usize = 32;
+pub const NUM_PATTERNS: usize = 8;
+
+#[derive(Clone)]
+pub struct Step {
+    pub on: bool,
+    pub note: i32,
+    pub vel: f32,
+    pub gate: f32,      // fraction of a step
+    pub slide: bool,    // glide from the step before instead of a new note
+    pub prob: f32,
+    pub locks: Patch,
+    pub lock_slot: usize,   // the slot the locks were turned on, the only one they apply to
+}
+
+impl Default for Step {
+    fn default() -> Self {
+        Step { on: false, note: 60, vel: 0.8, gate: 0.5, slide: false, prob: 1.0, locks: Patch::default(), lock_slot: 0 }
+    }
+}
+
+#[derive(Clone)]
+pub struct Pattern {
+    pub steps: Vec<Step>,
+    pub len: usize,
+}
+
+impl Default for Pattern {
+    fn default() -> Self {
+        Pattern { steps: vec![Step::default(); MAX_STEPS], len: 16 }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SeqNote {
+    pub pattern: usize,
+    pub step: usize,
+    pub on: u64,
+    pub off: Option<u64>,   // None when the next step slides out of this one
+}
+
+// Steps come off the transport, one tick each, so the song position (and the loop)
+// decides where in the chain we are.
+pub struct Sequencer {
+    pub patterns: Vec<Pattern>,
+    pub chain: Vec<usize>,  // empty plays the edited pattern round and round
+    pub edit: usize,
+    pub rate: u32,          // steps per beat
+    pub playing: bool,
+
+    seed: u32,
+}
+
+impl Default for Sequencer {
+    fn default() -> Self {
+        Sequencer {
+            patterns: vec![Pattern::default(); NUM_PATTERNS],
+            chain: vec![],
+            edit: 0,
+            rate: 4,
+            playing: false,
+            seed: 0,
+        }
+    }
+}
+
+impl Sequencer {
+    pub fn start(&mut self, seed: u32) {
+        self.playing = true;
+        self.seed = seed;
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    fn len(&self, pattern: usize) -> u64 {
+        self.patterns[pattern].len.clamp(1, MAX_STEPS) as u64
+    }
+
+    // (pattern, step) for a song position in steps
+    pub fn locate(&self, tick: u64) -> (usize, usize) {
+        if self.chain.is_empty() {
+            return (self.edit, (tick % self.len(self.edit)) as usize);
+        }
+        let total: u64 = self.chain.iter().map(|p| self.len(*p)).sum();
+        let mut t = tick % total;
+        for p in self.chain.iter() {
+            let len = self.len(*p);
+            if t < len {
+                return (*p, t as usize);
+            }
+            t -= len;
+        }
+        unreachable!()
+    }
+
+    // same tick, same answer, so a step can ask whether the next one is going to play
+    fn rolls(&self, step: &Step, raw: u64) -> bool {
+        step.on && (step.prob >= 1.0 || krand(self.seed.wrapping_add((raw as u32).wrapping_mul(7919))) < step.prob)
+    }
+
+    // step_len in samples, for the gates
+    pub fn run(&mut self, ticks: &[Tick], step_len: f64) -> Vec<SeqNote> {
+        let mut out = vec![];
+        if !self.playing {
+            return out;
+        }
+        for t in ticks {
+            let (p, s) = self.locate(t.pos);
+            let step = &self.patterns[p].steps[s];
+            if self.rolls(step, t.raw) {
+                let (np, ns) = self.locate(t.pos + 1);
+                let next = &self.patterns[np].steps[ns];
+                let tied = next.slide && self.rolls(next, t.raw + 1);
+                let off = if tied { None } else { Some(t.sample + (step.gate.clamp(0.05, 1.0) as f64 * step_len).max(1.0) as u64) };
+                out.push(SeqNote { pattern: p, step: s, on: t.sample, off });
+            }
+        }
+        out
+    }
+}
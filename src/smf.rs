@@ -0,0 +1,235 @@
+use std::path::Path;
+
+// Standard MIDI File writing, for getting what was played out into a DAW. Times come
+// in as seconds and go out as ticks at one tempo. Give it the time the transport's
+// bar 1 started and anything played to the transport lands on the grid.
+
+pub const PPQ: u16 = 480;
+
+// what made the note, type 1 files get a track each
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteSource {
+    Keys,
+    Arp,
+    Seq,
+}
+
+impl NoteSource {
+    pub const ALL: [NoteSource; 3] = [NoteSource::Keys, NoteSource::Arp, NoteSource::Seq];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NoteSource::Keys => "keys",
+            NoteSource::Arp => "arp",
+            NoteSource::Seq => "seq",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PlayedNote {
+    pub note: i32,
+    pub start: f32,     // seconds
+    pub end: f32,
+    pub vel: f32,       // 0..1
+    pub src: NoteSource,
+}
+
+// bpm counts beat_unit notes, midi tempo is always in quarters
+pub struct SmfTempo {
+    pub bpm: f32,
+    pub beats_per_bar: u32,
+    pub beat_unit: u32,
+}
+
+impl SmfTempo {
+    fn quarters_per_sec(&self) -> f64 {
+        self.bpm.max(1.0) as f64 / 60.0 * 4.0 / self.beat_unit.max(1) as f64
+    }
+}
+
+fn push_vlq(out: &mut Vec<u8>, mut x: u32) {
+    let mut bytes = vec![(x & 0x7f) as u8];
+    x >>= 7;
+    while x > 0 {
+        bytes.push((x & 0x7f) as u8 | 0x80);
+        x >>= 7;
+    }
+    out.extend(bytes.iter().rev());
+}
+
+// (tick, bytes) in any order, comes back as an MTrk chunk with end of track on
+fn track_chunk(mut events: Vec<(u32, Vec<u8>)>) -> Vec<u8> {
+    // stable, so at the same tick the offs that went in first stay first
+    events.sort_by_key(|(t, _)| *t);
+    let mut data = vec![];
+    let mut last = 0;
+    for (t, bytes) in events {
+        push_vlq(&mut data, t - last);
+        data.extend(bytes);
+        last = t;
+    }
+    data.extend([0x00, 0xff, 0x2f, 0x00]);
+    let mut chunk = b"MTrk".to_vec();
+    chunk.extend((data.len() as u32).to_be_bytes());
+    chunk.extend(data);
+    chunk
+}
+
+fn meta(kind: u8, bytes: &[u8]) -> Vec<u8> {
+    let mut m = vec![0xff, kind];
+    push_vlq(&mut m, bytes.len() as u32);
+    m.extend(bytes);
+    m
+}
+
+fn tempo_events(tempo: &SmfTempo) -> Vec<(u32, Vec<u8>)> {
+    let us = (1_000_000.0 / tempo.quarters_per_sec()).round() as u32;
+    let unit_log2 = tempo.beat_unit.max(1).trailing_zeros() as u8;
+    vec![
+        (0, meta(0x51, &us.to_be_bytes()[1..])),
+        (0, meta(0x58, &[tempo.beats_per_bar.clamp(1, 255) as u8, unit_log2, 24, 8])),
+    ]
+}
+
+fn note_events(notes: &[PlayedNote], t0: f32, tempo: &SmfTempo, channel: u8) -> Vec<(u32, Vec<u8>)> {
+    let to_tick = |t: f32| ((t - t0).max(0.0) as f64 * tempo.quarters_per_sec() * PPQ as f64).round() as u32;
+    // offs go in first so a note straight after the same note doesnt get cut
+    let mut offs = vec![];
+    let mut ons = vec![];
+    for n in notes {
+        let key = n.note.clamp(0, 127) as u8;
+        let vel = (n.vel * 127.0).round().clamp(1.0, 127.0) as u8;
+        let on = to_tick(n.start);
+        ons.push((on, vec![0x90 | channel, key, vel]));
+        offs.push((to_tick(n.end).max(on + 1), vec![0x80 | channel, key, 0x40]));
+    }
+    offs.extend(ons);
+    offs
+}
+
+// where time zero goes: origin if theres one, moved back whole bars until nothing is
+// before it. without one, the first note
+fn time_zero(notes: &[PlayedNote], tempo: &SmfTempo, origin: Option<f32>) -> f32 {
+    let first = notes.iter().map(|n| n.start).fold(f32::INFINITY, f32::min);
+    let first = if first.is_finite() { first } else { 0.0 };
+    match origin {
+        Some(origin) => {
+            let bar = (tempo.beats_per_bar.max(1) as f64 * 60.0 / tempo.bpm.max(1.0) as f64) as f32;
+            origin - ((origin - first) / bar).max(0.0).ceil() * bar
+        },
+        None => first,
+    }
+}
+
+// format 0 is one track with everything, format 1 is a tempo track then one per source
+pub fn write_smf(notes: &[PlayedNote], tempo: &SmfTempo, origin: Option<f32>, format: u16) -> Vec<u8> {
+    let t0 = time_zero(notes, tempo, origin);
+
+    let mut tracks = vec![];
+    if format == 0 {
+        let mut events = tempo_events(tempo);
+        events.extend(note_events(notes, t0, tempo, 0));
+        tracks.push(track_chunk(events));
+    } else {
+        tracks.push(track_chunk(tempo_events(tempo)));
+        for (i, src) in NoteSource::ALL.iter().enumerate() {
+            let these: Vec<PlayedNote> = notes.iter().filter(|n| n.src == *src).copied().collect();
+            if these.is_empty() {
+                continue;
+            }
+            let mut events = vec![(0, meta(0x03, src.name().as_bytes()))];
+            events.extend(note_events(&these, t0, tempo, i as u8));
+            tracks.push(track_chunk(events));
+        }
+    }
+
+    let mut out = b"MThd".to_vec();
+    out.extend(6u32.to_be_bytes());
+    out.extend(format.min(1).to_be_bytes());
+    out.extend((tracks.len() as u16).to_be_bytes());
+    out.extend(PPQ.to_be_bytes());
+    for t in tracks {
+        out.extend(t);
+    }
+    out
+}
+
+pub fn save_smf(path: &Path, notes: &[PlayedNote], tempo: &SmfTempo, origin: Option<f32>, format: u16) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+    std::fs::write(path, write_smf(notes, tempo, origin, format)).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEMPO: SmfTempo = SmfTempo { bpm: 120.0, beats_per_bar: 4, beat_unit: 4 };
+
+    fn note(note: i32, start: f32, end: f32, vel: f32, src: NoteSource) -> PlayedNote {
+        PlayedNote { note, start, end, vel, src }
+    }
+
+    #[test]
+    fn vlq() {
+        for (x, want) in [(0, vec![0x00]), (0x7f, vec![0x7f]), (0x80, vec![0x81, 0x00]), (480, vec![0x83, 0x60]),
+                          (0x0fff_ffff, vec![0xff, 0xff, 0xff, 0x7f])] {
+            let mut out = vec![];
+            push_vlq(&mut out, x);
+            assert_eq!(out, want, "{:#x}", x);
+        }
+    }
+
+    #[test]
+    fn type_0_two_notes() {
+        let notes = [note(60, 1.0, 1.5, 1.0, NoteSource::Keys), note(60, 1.5, 2.0, 0.5, NoteSource::Keys)];
+        let want: Vec<u8> = [
+            &b"MThd"[..], &[0, 0, 0, 6, 0, 0, 0, 1, 0x01, 0xe0],
+            b"MTrk", &[0, 0, 0, 0x25],
+            &[0x00, 0xff, 0x51, 0x03, 0x07, 0xa1, 0x20],
+            &[0x00, 0xff, 0x58, 0x04, 0x04, 0x02, 0x18, 0x08],
+            &[0x00, 0x90, 0x3c, 0x7f],
+            // the off goes before the retrigger on the same tick
+            &[0x83, 0x60, 0x80, 0x3c, 0x40],
+            &[0x00, 0x90, 0x3c, 0x40],
+            &[0x83, 0x60, 0x80, 0x3c, 0x40],
+            &[0x00, 0xff, 0x2f, 0x00],
+        ].concat();
+        assert_eq!(write_smf(&notes, &TEMPO, None, 0), want);
+    }
+
+    #[test]
+    fn type_1_track_per_source() {
+        let notes = [note(60, 1.0, 1.5, 1.0, NoteSource::Keys), note(64, 1.5, 2.0, 0.5, NoteSource::Seq)];
+        let want: Vec<u8> = [
+            &b"MThd"[..], &[0, 0, 0, 6, 0, 1, 0, 3, 0x01, 0xe0],
+            b"MTrk", &[0, 0, 0, 0x13],
+            &[0x00, 0xff, 0x51, 0x03, 0x07, 0xa1, 0x20],
+            &[0x00, 0xff, 0x58, 0x04, 0x04, 0x02, 0x18, 0x08],
+            &[0x00, 0xff, 0x2f, 0x00],
+            b"MTrk", &[0, 0, 0, 0x15],
+            &[0x00, 0xff, 0x03, 0x04], b"keys",
+            &[0x00, 0x90, 0x3c, 0x7f],
+            &[0x83, 0x60, 0x80, 0x3c, 0x40],
+            &[0x00, 0xff, 0x2f, 0x00],
+            // no arp notes so no arp track, seq keeps its own channel
+            b"MTrk", &[0, 0, 0, 0x15],
+            &[0x00, 0xff, 0x03, 0x03], b"seq",
+            &[0x83, 0x60, 0x92, 0x40, 0x40],
+            &[0x83, 0x60, 0x82, 0x40, 0x40],
+            &[0x00, 0xff, 0x2f, 0x00],
+        ].concat();
+        assert_eq!(write_smf(&notes, &TEMPO, None, 1), want);
+    }
+
+    #[test]
+    fn zero_on_the_bar() {
+        let notes = [note(60, 1.0, 1.5, 1.0, NoteSource::Keys)];
+        // bars are 2s at 120, a note just before bar 1 pulls zero back a whole bar
+        assert!((time_zero(&notes, &TEMPO, Some(1.25)) - -0.75).abs() < 1e-6);
+        assert!((time_zero(&notes, &TEMPO, Some(0.5)) - 0.5).abs() < 1e-6);
+        assert!((time_zero(&notes, &TEMPO, None) - 1.0).abs() < 1e-6);
+    }
+}
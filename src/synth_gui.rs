@@ -1,17 +1,64 @@
 use crate::audio::*;
+use crate::effects::*;
+use crate::effects::eq::*;
+use crate::effects::formant::NUM_VOWELS;
 use crate::kapp::*;
 use crate::kmath::*;
 use crate::texture_buffer::TextureBuffer;
 use crate::widgets::*;
+use crate::voices::*;
+use crate::voices::osc::Wave;
+use crate::voices::morph::*;
+use crate::voices::sampler::*;
+use crate::voices::granular::GrainWindow;
+use crate::voices::drums::DrumKind;
+use crate::voices::sfx::*;
+use crate::patch::*;
+use crate::tuning::*;
+use crate::note::*;
+use crate::keymap::*;
+use crate::arp::*;
+use crate::seq::*;
+use crate::transport::*;
+use crate::smf::*;
+use crate::dsp::SAMPLE_RATE;
+use crate::wav::*;
 
 use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
 
 use rustfft::num_complex::ComplexFloat;
 use rustfft::{FftPlanner, num_complex::Complex};
 
 const FFT_SIZE: usize = 8192;
-const NUM_NOTES: usize = 34;
 
+// the eq display goes +- this
+const EQ_DISPLAY_DB: f32 = 24.0;
+
+// tuning 0 is always 12-TET, the rest get filled by dropping .scl files
+const NUM_TUNINGS: usize = 8;
+
+// where the save buttons put patches
+const PATCH_DIR: &str = "patches";
+// and where the note history gets exported to
+const EXPORT_DIR: &str = "exports";
+
+// morph timeline, held part then release part
+const MORPH_DISPLAY_S: f32 = 4.0;
+const MORPH_RELEASE_S: f32 = 2.0;
+const MORPH_SPLIT: f32 = 0.8;
+
+// spectrum and eq x axis, 20hz to 20khz log
+fn x_to_freq(x: f32) -> f32 {
+    20.0 * 1000.0f32.powf(x)
+}
+
+fn freq_to_x(f: f32) -> f32 {
+    (f / 20.0).log10() / 3.0
+}
+
+#[derive(Clone)]
 pub struct Knobs {
     pub a: Knob,
     pub d: Knob,
@@ -22,7 +69,7 @@ pub struct Knobs {
     pub troll: Knob,
     pub detune: Knob,
     pub voices: Knob,
-    pub base_freq: Knob,
+    pub coarse: Knob,           // semitones
     
     pub amp: Knob,
     pub cut: Knob,
@@ -31,11 +78,65 @@ pub struct Knobs {
     pub cdr: Knob,
     pub hard_clip: Knob,
 
-    pub aout: Knob,
+    pub dist_curve: Knob,
+    pub dist_drive: Knob,
+    pub dist_bias: Knob,
+    pub dist_os: Knob,
+    pub crush_bits: Knob,
+    pub crush_rate: Knob,
+
+    pub voice: Knob,
+    pub str_damp: Knob,
+    pub str_pick: Knob,
+    pub str_excite: Knob,
+
+    pub fmt_vowel: Knob,
+    pub fmt_rate: Knob,
+    pub fmt_depth: Knob,
+    pub fmt_mix: Knob,
+
+    pub osc2_wave: Knob,
+    pub osc2_semi: Knob,
+    pub osc2_fine: Knob,
+    pub osc2_mix: Knob,
+    pub osc2_sync: Knob,
+    pub osc2_ring: Knob,
+    pub sub_level: Knob,
+    pub sub_oct: Knob,
+
+    // edited on the timeline rather than with knobs
+    pub morph: MorphDesc,
+
+    pub smp_id: Knob,
+    pub smp_root: Knob,
+    pub smp_start: Knob,
+    pub smp_end: Knob,
+    pub smp_loop: Knob,
+    pub smp_xfade: Knob,
+
+    pub gr_size: Knob,
+    pub gr_density: Knob,
+    pub gr_pos: Knob,
+    pub gr_pos_jit: Knob,
+    pub gr_pitch_jit: Knob,
+    pub gr_spray: Knob,
+    pub gr_window: Knob,
+    pub gr_scan: Knob,
+
+    pub drum_kind: Knob,
+    pub drum_decay: Knob,
+    pub drum_tone: Knob,
+    pub drum_punch: Knob,
+
+    pub flt_cut: Knob,
+    pub flt_res: Knob,
+
+    // SFX_PARAM_DESCS order
+    pub sfx: Vec<Knob>,
 }
 
 impl Knobs {
-    fn get_sd(&self, f: f32) -> SoundDesc {
+    fn get_sd(&self, f: f32, a4: f32) -> SoundDesc {
         SoundDesc {
             f,
             n: self.n.curr(),
@@ -52,6 +153,152 @@ impl Knobs {
             cdt: self.cdt.curr(),
             cdr: self.cdr.curr(),
             hard_clip: self.hard_clip.curr(),
+            dist_curve: self.dist_curve.curr(),
+            dist_drive: self.dist_drive.curr(),
+            dist_bias: self.dist_bias.curr(),
+            dist_os: self.dist_os.curr(),
+            crush_bits: self.crush_bits.curr(),
+            crush_rate: self.crush_rate.curr(),
+            voice: self.voice.curr(),
+            str_damp: self.str_damp.curr(),
+            str_pick: self.str_pick.curr(),
+            str_excite: self.str_excite.curr(),
+            fmt_vowel: self.fmt_vowel.curr(),
+            fmt_rate: self.fmt_rate.curr(),
+            fmt_depth: self.fmt_depth.curr(),
+            fmt_mix: self.fmt_mix.curr(),
+            osc2_wave: self.osc2_wave.curr(),
+            osc2_semi: self.osc2_semi.curr(),
+            osc2_fine: self.osc2_fine.curr(),
+            osc2_mix: self.osc2_mix.curr(),
+            osc2_sync: self.osc2_sync.curr(),
+            osc2_ring: self.osc2_ring.curr(),
+            sub_level: self.sub_level.curr(),
+            sub_oct: self.sub_oct.curr(),
+            morph: self.morph,
+            smp_id: self.smp_id.curr(),
+            // root is a key like the ones you play, so same base frequency
+            smp_root: midi_to_freq(self.smp_root.curr().round(), a4),
+            smp_start: self.smp_start.curr(),
+            smp_end: self.smp_end.curr(),
+            smp_loop: self.smp_loop.curr(),
+            smp_xfade: self.smp_xfade.curr(),
+            gr_size: self.gr_size.curr(),
+            gr_density: self.gr_density.curr(),
+            gr_pos: self.gr_pos.curr(),
+            gr_pos_jit: self.gr_pos_jit.curr(),
+            gr_pitch_jit: self.gr_pitch_jit.curr(),
+            gr_spray: self.gr_spray.curr(),
+            gr_window: self.gr_window.curr(),
+            gr_scan: self.gr_scan.curr(),
+            drum_kind: self.drum_kind.curr(),
+            drum_decay: self.drum_decay.curr(),
+            drum_tone: self.drum_tone.curr(),
+            drum_punch: self.drum_punch.curr(),
+            flt_cut: self.flt_cut.curr(),
+            flt_res: self.flt_res.curr(),
+            vel: 1.0,
+            sfx: {
+                let mut p = [0.0; SFX_PARAMS];
+                for (i, k) in self.sfx.iter().enumerate() {
+                    p[i] = k.curr();
+                }
+                p
+            },
+        }
+    }
+
+    // every knob with the name it goes by in a patch file
+    fn named_mut(&mut self) -> Vec<(String, &mut Knob)> {
+        let mut v: Vec<(String, &mut Knob)> = vec![
+            ("a".to_string(), &mut self.a),
+            ("d".to_string(), &mut self.d),
+            ("s".to_string(), &mut self.s),
+            ("r".to_string(), &mut self.r),
+            ("n".to_string(), &mut self.n),
+            ("troll".to_string(), &mut self.troll),
+            ("detune".to_string(), &mut self.detune),
+            ("voices".to_string(), &mut self.voices),
+            ("coarse".to_string(), &mut self.coarse),
+            ("amp".to_string(), &mut self.amp),
+            ("cut".to_string(), &mut self.cut),
+            ("cur".to_string(), &mut self.cur),
+            ("cdt".to_string(), &mut self.cdt),
+            ("cdr".to_string(), &mut self.cdr),
+            ("hard_clip".to_string(), &mut self.hard_clip),
+            ("dist_curve".to_string(), &mut self.dist_curve),
+            ("dist_drive".to_string(), &mut self.dist_drive),
+            ("dist_bias".to_string(), &mut self.dist_bias),
+            ("dist_os".to_string(), &mut self.dist_os),
+            ("crush_bits".to_string(), &mut self.crush_bits),
+            ("crush_rate".to_string(), &mut self.crush_rate),
+            ("voice".to_string(), &mut self.voice),
+            ("str_damp".to_string(), &mut self.str_damp),
+            ("str_pick".to_string(), &mut self.str_pick),
+            ("str_excite".to_string(), &mut self.str_excite),
+            ("fmt_vowel".to_string(), &mut self.fmt_vowel),
+            ("fmt_rate".to_string(), &mut self.fmt_rate),
+            ("fmt_depth".to_string(), &mut self.fmt_depth),
+            ("fmt_mix".to_string(), &mut self.fmt_mix),
+            ("osc2_wave".to_string(), &mut self.osc2_wave),
+            ("osc2_semi".to_string(), &mut self.osc2_semi),
+            ("osc2_fine".to_string(), &mut self.osc2_fine),
+            ("osc2_mix".to_string(), &mut self.osc2_mix),
+            ("osc2_sync".to_string(), &mut self.osc2_sync),
+            ("osc2_ring".to_string(), &mut self.osc2_ring),
+            ("sub_level".to_string(), &mut self.sub_level),
+            ("sub_oct".to_string(), &mut self.sub_oct),
+            ("smp_id".to_string(), &mut self.smp_id),
+            ("smp_root".to_string(), &mut self.smp_root),
+            ("smp_start".to_string(), &mut self.smp_start),
+            ("smp_end".to_string(), &mut self.smp_end),
+            ("smp_loop".to_string(), &mut self.smp_loop),
+            ("smp_xfade".to_string(), &mut self.smp_xfade),
+            ("gr_size".to_string(), &mut self.gr_size),
+            ("gr_density".to_string(), &mut self.gr_density),
+            ("gr_pos".to_string(), &mut self.gr_pos),
+            ("gr_pos_jit".to_string(), &mut self.gr_pos_jit),
+            ("gr_pitch_jit".to_string(), &mut self.gr_pitch_jit),
+            ("gr_spray".to_string(), &mut self.gr_spray),
+            ("gr_window".to_string(), &mut self.gr_window),
+            ("gr_scan".to_string(), &mut self.gr_scan),
+            ("drum_kind".to_string(), &mut self.drum_kind),
+            ("drum_decay".to_string(), &mut self.drum_decay),
+            ("drum_tone".to_string(), &mut self.drum_tone),
+            ("drum_punch".to_string(), &mut self.drum_punch),
+            ("flt_cut".to_string(), &mut self.flt_cut),
+            ("flt_res".to_string(), &mut self.flt_res),
+        ];
+        for (k, d) in self.sfx.iter_mut().zip(SFX_PARAM_DESCS.iter()) {
+            v.push((format!("sfx_{}", d.name.replace(' ', "_")), k));
+        }
+        v
+    }
+
+    // takes &mut because it reads the knobs through named_mut
+    pub fn build_patch(&mut self) -> Patch {
+        let morph = self.morph;
+        Patch {
+            values: self.named_mut().into_iter().map(|(n, k)| (n, k.curr())).collect(),
+            morph: Some(morph),
+        }
+    }
+
+    // knobs the patch doesnt mention are left alone
+    pub fn apply_patch(&mut self, patch: &Patch) {
+        for (name, knob) in self.named_mut() {
+            if let Some(v) = patch.get(&name) {
+                knob.set_val(v);
+            }
+        }
+        if let Some(morph) = patch.morph {
+            self.morph = morph;
+        }
+    }
+
+    fn set_sfx(&mut self, p: &SfxParams) {
+        for (k, v) in self.sfx.iter_mut().zip(p.iter()) {
+            k.set_val(*v);
         }
     }
 }
@@ -68,8 +315,7 @@ impl Default for Knobs {
             troll: Knob::new(2.0, 1.0, 5.0, 0.001, "Exponent"),
             voices: Knob::new(1.0, 1.0, 9.0, 0.001, "Voices"),
             detune: Knob::new(0.0, 0.0, 99.0, 0.001, "Detune"),
-            aout: Knob::new(-10.0, -80.0, 20.0, 0.001, "volume"),
-            base_freq: Knob::new(110.0, 20.0, 880.0, 0.001, "Base Frequency"),
+            coarse: Knob::new(0.0, -24.0, 24.0, 0.001, "coarse"),
 
             amp: Knob::new(-30.0, -60.0, 30.0, 0.001, "Amplitude"),
             cut: Knob::new(-100.0, -100.0, 0.0, 0.001, "up threshold"),
@@ -77,18 +323,339 @@ impl Default for Knobs {
             cdt: Knob::new(0.0, -100.0, 0.0, 0.001, "down threshold"),
             cdr: Knob::new(1.0, 1.0, 16.0, 0.001, "down ratio"),
             hard_clip: Knob::new(0.0, -100.0, 0.0, 0.001, "hard clip db"),
-            
+
+            dist_curve: Knob::new(0.0, 0.0, 3.0, 0.001, "curve"),
+            dist_drive: Knob::new(0.0, 0.0, 40.0, 0.001, "drive db"),
+            dist_bias: Knob::new(0.0, -1.0, 1.0, 0.001, "bias"),
+            dist_os: Knob::new(2.0, 1.0, 8.0, 0.001, "oversample"),
+            crush_bits: Knob::new(16.0, 1.0, 16.0, 0.001, "bits"),
+            crush_rate: Knob::new(1.0, 1.0, 64.0, 0.001, "downsample"),
+
+            voice: Knob::new(0.0, 0.0, (VoiceKind::ALL.len() - 1) as f32, 0.001, "voice"),
+            str_damp: Knob::new(0.5, 0.0, 1.0, 0.001, "damping"),
+            str_pick: Knob::new(0.1, 0.0, 0.5, 0.001, "pick pos"),
+            str_excite: Knob::new(0.0, 0.0, 1.0, 0.001, "excite"),
+
+            fmt_vowel: Knob::new(0.0, 0.0, (NUM_VOWELS - 1) as f32, 0.001, "vowel"),
+            fmt_rate: Knob::new(0.0, 0.0, 8.0, 0.001, "vowel rate"),
+            fmt_depth: Knob::new(1.0, 0.0, 2.0, 0.001, "vowel depth"),
+            fmt_mix: Knob::new(0.0, 0.0, 1.0, 0.001, "vowel mix"),
+
+            osc2_wave: Knob::new(1.0, 0.0, (Wave::ALL.len() - 1) as f32, 0.001, "osc2 wave"),
+            osc2_semi: Knob::new(0.0, -24.0, 24.0, 0.001, "osc2 semi"),
+            osc2_fine: Knob::new(0.0, -100.0, 100.0, 0.001, "osc2 fine"),
+            osc2_mix: Knob::new(0.0, 0.0, 1.0, 0.001, "osc2 mix"),
+            osc2_sync: Knob::new(0.0, 0.0, 1.0, 0.001, "sync"),
+            osc2_ring: Knob::new(0.0, 0.0, 1.0, 0.001, "ring"),
+            sub_level: Knob::new(0.0, 0.0, 1.0, 0.001, "sub"),
+            sub_oct: Knob::new(1.0, 1.0, 2.0, 0.001, "sub oct"),
+
+            morph: MorphDesc::default(),
+
+            smp_id: Knob::new(0.0, 0.0, (NUM_SAMPLES - 1) as f32, 0.001, "sample"),
+            smp_root: Knob::new(57.0, 0.0, 127.0, 0.001, "root key"),
+            smp_start: Knob::new(0.0, 0.0, 1.0, 0.001, "start"),
+            smp_end: Knob::new(1.0, 0.0, 1.0, 0.001, "end"),
+            smp_loop: Knob::new(0.0, 0.0, 1.0, 0.001, "loop"),
+            smp_xfade: Knob::new(0.0, 0.0, 0.5, 0.001, "crossfade"),
+
+            gr_size: Knob::new(80.0, 5.0, 500.0, 0.001, "grain ms"),
+            gr_density: Knob::new(20.0, 1.0, 200.0, 0.001, "density"),
+            gr_pos: Knob::new(0.0, 0.0, 1.0, 0.001, "position"),
+            gr_pos_jit: Knob::new(0.02, 0.0, 1.0, 0.001, "pos jitter"),
+            gr_pitch_jit: Knob::new(0.0, 0.0, 12.0, 0.001, "pitch jitter"),
+            gr_spray: Knob::new(0.2, 0.0, 1.0, 0.001, "spray"),
+            gr_window: Knob::new(0.0, 0.0, (GrainWindow::ALL.len() - 1) as f32, 0.001, "window"),
+            gr_scan: Knob::new(0.0, -2.0, 2.0, 0.001, "scan"),
+
+            drum_kind: Knob::new(0.0, 0.0, (DrumKind::ALL.len() - 1) as f32, 0.001, "drum"),
+            drum_decay: Knob::new(0.4, 0.01, 2.0, 0.001, "decay"),
+            drum_tone: Knob::new(0.3, 0.0, 1.0, 0.001, "tone"),
+            drum_punch: Knob::new(0.6, 0.0, 1.0, 0.001, "punch"),
+
+            flt_cut: Knob::new(20000.0, 20.0, 20000.0, 0.001, "cutoff"),
+            flt_res: Knob::new(0.707, 0.5, 10.0, 0.001, "res"),
+
+            sfx: SFX_PARAM_DESCS.iter().map(|d| Knob::new(d.default, d.min, d.max, 0.001, d.name)).collect(),
+
+        }
+    }
+}
+
+// One patch with its own voices in the mixer, plays the notes between lo and hi
+pub struct InstrumentSlot {
+    pub knobs: Knobs,
+    pub vol: Knob,
+    pub pan: Knob,
+    pub lo: Knob,
+    pub hi: Knob,
+    pub sends: Vec<Knob>,
+    pub ghost: bool,
+    pub tuning: Knob,
+}
+
+impl InstrumentSlot {
+    fn new(lo: i32, hi: i32) -> InstrumentSlot {
+        InstrumentSlot {
+            knobs: Knobs::default(),
+            vol: Knob::new(0.0, -60.0, 12.0, 0.001, "vol"),
+            pan: Knob::new(0.0, -1.0, 1.0, 0.001, "pan"),
+            lo: Knob::new(lo as f32, 0.0, 127.0, 0.001, "lo"),
+            hi: Knob::new(hi as f32, 0.0, 127.0, 0.001, "hi"),
+            // the dry send stays at 1, only the aux sends get knobs
+            sends: (0..NUM_BUSES).map(|b| Knob::new(if b == 0 { 1.0 } else { 0.0 }, 0.0, 1.0, 0.001, &format!("aux {}", b))).collect(),
+            ghost: false,
+            tuning: Knob::new(0.0, 0.0, (NUM_TUNINGS - 1) as f32, 0.001, "tuning"),
+        }
+    }
+
+    fn tuning_id(&self) -> usize {
+        self.tuning.curr().round() as usize
+    }
+
+    // lo above hi means the slot is off
+    fn plays(&self, note: i32) -> bool {
+        self.lo.curr().round() as i32 <= note && note <= self.hi.curr().round() as i32
+    }
+}
+
+// longest an sfx preview gets before its let go
+const SFX_PREVIEW_SECS: f32 = 2.0;
+
+// how far ahead of the audio clock the arp schedules, has to cover a slow frame
+const ARP_LOOKAHEAD: f32 = 0.1;
+
+pub struct ArpPanel {
+    pub on: bool,
+    pub latch: bool,
+    pub mode: Knob,
+    pub octaves: Knob,
+    pub rate: Knob,
+    pub gate: Knob,
+    pub swing: Knob,
+}
+
+impl Default for ArpPanel {
+    fn default() -> Self {
+        ArpPanel {
+            on: false,
+            latch: false,
+            mode: Knob::new(0.0, 0.0, (ArpMode::ALL.len() - 1) as f32, 0.001, "mode"),
+            octaves: Knob::new(1.0, 1.0, 4.0, 0.001, "octaves"),
+            rate: Knob::new(3.0, 0.0, (ARP_RATES.len() - 1) as f32, 0.001, "rate"),
+            gate: Knob::new(0.5, 0.05, 1.0, 0.001, "gate"),
+            swing: Knob::new(0.0, 0.0, 0.75, 0.001, "swing"),
+        }
+    }
+}
+
+impl ArpPanel {
+    fn apply(&self, arp: &mut Arp) {
+        arp.mode = ArpMode::from_param(self.mode.curr());
+        arp.octaves = self.octaves.curr().round() as usize;
+        arp.rate = ARP_RATES[self.rate.curr().round() as usize];
+        arp.gate = self.gate.curr();
+        arp.swing = self.swing.curr();
+        arp.set_latch(self.latch);
+    }
+}
+
+pub struct TransportPanel {
+    pub metronome: bool,
+    pub bpm: Knob,
+    pub beats: Knob,
+    pub unit: Knob,
+    pub loop_start: Knob,
+    pub loop_len: Knob,
+}
+
+impl Default for TransportPanel {
+    fn default() -> Self {
+        TransportPanel {
+            metronome: false,
+            bpm: Knob::new(120.0, 40.0, 240.0, 0.001, "bpm"),
+            beats: Knob::new(4.0, 1.0, 12.0, 0.001, "beats"),
+            unit: Knob::new(1.0, 0.0, (BEAT_UNITS.len() - 1) as f32, 0.001, "unit"),
+            loop_start: Knob::new(0.0, 0.0, 63.0, 0.001, "loop bar"),
+            loop_len: Knob::new(4.0, 1.0, 16.0, 0.001, "loop bars"),
+        }
+    }
+}
+
+impl TransportPanel {
+    // tempo goes through set_bpm so the song position doesnt jump
+    fn apply(&self, transport: &mut Transport, now: u64) {
+        transport.set_bpm(self.bpm.curr(), now);
+        transport.beats_per_bar = self.beats.curr().round() as u32;
+        transport.beat_unit = BEAT_UNITS[self.unit.curr().round() as usize];
+        transport.loop_start = self.loop_start.curr().round() as u32;
+        transport.loop_len = self.loop_len.curr().round() as u32;
+    }
+}
+
+// One shot drum on a function key, plays through the selected slot
+pub struct DrumPad {
+    pub kind: DrumKind,
+    pub key: VirtualKeyCode,
+    pub tune: Knob,
+    pub decay: Knob,
+    pub tone: Knob,
+    pub punch: Knob,
+}
+
+impl DrumPad {
+    fn new(kind: DrumKind, key: VirtualKeyCode) -> DrumPad {
+        let (tune, decay, tone, punch) = kind.defaults();
+        DrumPad {
+            kind,
+            key,
+            tune: Knob::new(tune, 20.0, 2000.0, 0.001, "tune"),
+            decay: Knob::new(decay, 0.01, 2.0, 0.001, "decay"),
+            tone: Knob::new(tone, 0.0, 1.0, 0.001, "tone"),
+            punch: Knob::new(punch, 0.0, 1.0, 0.001, "punch"),
+        }
+    }
+
+    // the slots sound with the drum bits swapped in
+    fn get_sd(&self, knobs: &Knobs, a4: f32) -> SoundDesc {
+        let mut sd = knobs.get_sd(self.tune.curr(), a4);
+        sd.voice = VoiceKind::ALL.iter().position(|k| *k == VoiceKind::Drum).unwrap() as f32;
+        sd.drum_kind = DrumKind::ALL.iter().position(|k| *k == self.kind).unwrap() as f32;
+        sd.drum_decay = self.decay.curr();
+        sd.drum_tone = self.tone.curr();
+        sd.drum_punch = self.punch.curr();
+        sd
+    }
+}
+
+
+// where custom keyboard layouts live, any .keymap in here is loaded at startup
+const KEYMAP_DIR: &str = "keymaps";
+
+fn load_layouts() -> Vec<KeyLayout> {
+    let mut layouts = KeyLayout::builtin();
+    if let Ok(dir) = std::fs::read_dir(KEYMAP_DIR) {
+        let mut paths: Vec<_> = dir.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.extension().is_some_and(|e| e == "keymap")).collect();
+        paths.sort();
+        for path in paths {
+            match KeyLayout::load(&path) {
+                Ok(layout) => layouts.push(layout),
+                Err(e) => println!("couldnt load {}: {}", path.display(), e),
+            }
+        }
+    }
+    layouts
+}
+
+// note name along the bottom of a knob
+fn note_label(outputs: &mut FrameOutputs, r: Rect, midi: i32) {
+    let w = 0.08 * r.h;
+    outputs.glyphs.push_center_str(&note_name(midi), r.x + r.w/2.0, r.y + r.h - w, w, w, 1.2, v4(1.0, 1.0, 1.0, 1.0));
+}
+
+pub struct BusStrip {
+    pub fader: Knob,
+    pub mute: bool,
+    pub solo: bool,
+}
+
+impl Default for BusStrip {
+    fn default() -> Self {
+        BusStrip {
+            fader: Knob::new(0.0, -60.0, 12.0, 0.001, "fader"),
+            mute: false,
+            solo: false,
+        }
+    }
+}
+
+// rack 0 is the master chain, the rest are the buses
+fn rack_chain(rack: usize) -> ChainId {
+    if rack == 0 { ChainId::Master } else { ChainId::Bus(rack - 1) }
+}
+
+fn rack_name(rack: usize) -> &'static str {
+    match rack {
+        0 => "master",
+        1 => "dry",
+        2 => "aux 1",
+        3 => "aux 2",
+        _ => "aux",
+    }
+}
+
+// params that are really switches get buttons in the rack
+fn rack_toggles(name: &str) -> &'static [usize] {
+    match name {
+        "delay" => &delay::TOGGLES,
+        _ => &[],
+    }
+}
+
+// GUI side mirror of an insert on one of the chains
+pub struct RackSlot {
+    pub id: u64,
+    pub name: &'static str,
+    pub bypass: bool,
+    pub knobs: Vec<Knob>,
+}
+
+impl RackSlot {
+    fn new(id: u64, effect: &dyn Effect) -> RackSlot {
+        RackSlot {
+            id,
+            name: effect.name(),
+            bypass: false,
+            knobs: effect.params().iter().map(|p| Knob::new(p.default, p.min, p.max, 0.001, p.name)).collect(),
         }
     }
 }
 
 pub struct SynthGUI {
-    knobs: Knobs,
+    slots: Vec<InstrumentSlot>,
+    selected: usize,
+    aout: Knob,
+
+    // master chain then one per bus
+    racks: Vec<Vec<RackSlot>>,
+    rack_sel: usize,
+    bus_strips: Vec<BusStrip>,
+    next_effect_id: u64,
+    eq_drag: Option<usize>,
+    morph_field: usize,
+    morph_drag: Option<usize>,      // MAX_KEYFRAMES is the release frame
+    sample_names: Vec<Option<String>>,
+    tunings: Vec<Option<Tuning>>,
+    pads: Vec<DrumPad>,
+    sfx_cat: usize,
+    sfx_seed: u32,
+    sfx_locks: [bool; SFX_PARAMS],
+
+    shift: KeyShift,
+    layouts: Vec<KeyLayout>,
+    layout: usize,
+    a4: Knob,
+    transpose: Knob,
+
+    arp: Arp,
+    arp_panel: ArpPanel,
+    arp_keys: HashMap<VirtualKeyCode, i32>,
+    arp_pending: Vec<(u64, i32, bool)>,    // sequencer notes on their way to the arp, sample, note, down
 
-    history: Vec<(usize, f32, f32)>,
+    transport: Transport,
+    transport_panel: TransportPanel,
+    last_tick: Option<u64>,     // raw transport tick scheduled last, so nothing goes out twice
 
-    held_keys: HashMap<u32, (usize, f32, SoundDesc)>,
-    times_pressed: HashMap<VirtualKeyCode, u32>,
+    seq: Sequencer,
+    seq_view: bool,
+    seq_voices: Vec<(VoiceHandle, usize)>,     // voices the next slide step glides
+    seq_lock: Option<usize>,                    // step in the edit pattern the knobs are locking
+    seq_low: i32,                               // bottom row of the grid
+
+    history: Vec<PlayedNote>,
+
+    held_keys: HashMap<VoiceHandle, (i32, f32, SoundDesc)>,
+    key_voices: HashMap<VirtualKeyCode, Vec<(VoiceHandle, usize)>>,     // one per slot the key layers onto
 
     local_mixer: Mixer,
     sample_ringbuf: [f32; FFT_SIZE],
@@ -98,10 +665,43 @@ pub struct SynthGUI {
 impl Default for SynthGUI {
     fn default() -> Self {
         SynthGUI {
-            knobs: Knobs::default(),
+            // first slot gets the whole keyboard, the rest start off
+            slots: (0..NUM_SLOTS).map(|i| if i == 0 { InstrumentSlot::new(0, 127) } else { InstrumentSlot::new(127, 0) }).collect(),
+            selected: 0,
+            aout: Knob::new(-10.0, -80.0, 20.0, 0.001, "volume"),
+            racks: (0..NUM_BUSES + 1).map(|_| Vec::new()).collect(),
+            rack_sel: 0,
+            bus_strips: (0..NUM_BUSES).map(|_| BusStrip::default()).collect(),
+            next_effect_id: 1,
+            eq_drag: None,
+            morph_field: 2,
+            morph_drag: None,
+            sample_names: vec![None; NUM_SAMPLES],
+            tunings: (0..NUM_TUNINGS).map(|i| if i == 0 { Some(Tuning::default()) } else { None }).collect(),
+            pads: DrumKind::ALL.iter().zip(PAD_KEYS.iter()).map(|(kind, key)| DrumPad::new(*kind, *key)).collect(),
+            sfx_cat: 0,
+            sfx_seed: 0,
+            sfx_locks: [false; SFX_PARAMS],
+            shift: KeyShift::default(),
+            layouts: load_layouts(),
+            layout: 0,
+            a4: Knob::new(DEFAULT_A4_FREQ, 415.0, 466.0, 0.001, "A4"),
+            transpose: Knob::new(0.0, -12.0, 12.0, 0.001, "transpose"),
+            arp: Arp::default(),
+            arp_panel: ArpPanel::default(),
+            arp_keys: HashMap::new(),
+            arp_pending: vec![],
+            transport: Transport::default(),
+            transport_panel: TransportPanel::default(),
+            last_tick: None,
+            seq: Sequencer::default(),
+            seq_view: false,
+            seq_voices: vec![],
+            seq_lock: None,
+            seq_low: 48,
             history: Vec::new(),
             held_keys: HashMap::new(),
-            times_pressed: HashMap::new(),
+            key_voices: HashMap::new(),
             local_mixer: Mixer::default(),
             sample_ringbuf: [0.0; FFT_SIZE],
             rb_head: 0,
@@ -109,113 +709,1004 @@ impl Default for SynthGUI {
     }
 }
 
-fn kc_to_note(kc: VirtualKeyCode) -> Option<usize> {
-    match kc {
-        VirtualKeyCode::Z => Some(0),
-        VirtualKeyCode::X => Some(1),
-        VirtualKeyCode::C => Some(2),
-        VirtualKeyCode::V => Some(3),
-        VirtualKeyCode::B => Some(4),
-        VirtualKeyCode::N => Some(5),
-        VirtualKeyCode::M => Some(6),
-        VirtualKeyCode::Comma => Some(7),
-        VirtualKeyCode::Period => Some(8),
-        VirtualKeyCode::Slash => Some(9),
-        VirtualKeyCode::RShift => Some(10),
-
-        VirtualKeyCode::Capital => Some(6),
-        VirtualKeyCode::A => Some(7),
-        VirtualKeyCode::S => Some(8),
-        VirtualKeyCode::D => Some(9),
-        VirtualKeyCode::F => Some(10),
-        VirtualKeyCode::G => Some(11),
-        VirtualKeyCode::H => Some(12),
-        VirtualKeyCode::J => Some(13),
-        VirtualKeyCode::K => Some(14),
-        VirtualKeyCode::L => Some(15),
-        VirtualKeyCode::Semicolon => Some(16),
-        VirtualKeyCode::Apostrophe => Some(17),
-        VirtualKeyCode::Return => Some(18),
-
-        VirtualKeyCode::Tab => Some(13),
-        VirtualKeyCode::Q => Some(14),
-        VirtualKeyCode::W => Some(15),
-        VirtualKeyCode::E => Some(16),
-        VirtualKeyCode::R => Some(17),
-        VirtualKeyCode::T => Some(18),
-        VirtualKeyCode::Y => Some(19),
-        VirtualKeyCode::U => Some(20),
-        VirtualKeyCode::I => Some(21),
-        VirtualKeyCode::O => Some(22),
-        VirtualKeyCode::P => Some(23),
-        VirtualKeyCode::LBracket => Some(24),
-        VirtualKeyCode::RBracket => Some(25),
-        VirtualKeyCode::Backslash => Some(26),
-
-        VirtualKeyCode::Escape => Some(20),
-        VirtualKeyCode::Key1 => Some(21),
-        VirtualKeyCode::Key2 => Some(22),
-        VirtualKeyCode::Key3 => Some(23),
-        VirtualKeyCode::Key4 => Some(24),
-        VirtualKeyCode::Key5 => Some(25),
-        VirtualKeyCode::Key6 => Some(26),
-        VirtualKeyCode::Key7 => Some(27),
-
-        VirtualKeyCode::Key8 => Some(28),
-        VirtualKeyCode::Key9 => Some(29),
-        VirtualKeyCode::Key0 => Some(30),
-        VirtualKeyCode::Minus => Some(31),
-        VirtualKeyCode::Equals => Some(32),
-        VirtualKeyCode::Back => Some(33),
-
-        _ => None,
+impl SynthGUI {
+    // the end of the frame copies everything to the local mixer, whether it went through
+    // here or one of the FrameOutputs helpers
+    fn send(&mut self, outputs: &mut FrameOutputs, com: AudioCommand) {
+        outputs.sounds.push(com);
+    }
+
+    // adds to whichever chain the rack is showing
+    fn add_effect(&mut self, outputs: &mut FrameOutputs, kind: EffectKind) {
+        if self.racks[self.rack_sel].len() >= MAX_INSERTS {
+            return;
+        }
+        let id = self.next_effect_id;
+        self.next_effect_id += 1;
+        let effect = kind.make();
+        self.racks[self.rack_sel].push(RackSlot::new(id, effect.as_ref()));
+        self.send(outputs, AudioCommand::AddEffect(id, rack_chain(self.rack_sel), effect));
+    }
+
+    // slot select, mix and key range, master volume at the end
+    fn slots_frame(&mut self, inputs: &FrameInputState, outputs: &mut FrameOutputs, r: Rect) {
+        let r = r.dilate_pc(-0.01);
+        let mut coms = vec![];
+        for i in 0..NUM_SLOTS {
+            let r = r.grid_child(i as i32, 0, NUM_SLOTS as i32 + 1, 1).dilate_pc(-0.02);
+            let c = if i == self.selected { Vec4::new(0.9, 0.2, 0.2, 1.0) } else { Vec4::new(0.5, 0.1, 0.1, 1.0) };
+            outputs.canvas.put_rect(r, 1.01, c);
+            if button(inputs, outputs, r.child(0.0, 0.0, 0.16, 0.6).dilate_pc(-0.1), &format!("{}", i + 1), i == self.selected) {
+                self.selected = i;
+            }
+            let slot = &mut self.slots[i];
+            // ghost slots are silent and just there to key sidechains
+            if button(inputs, outputs, r.child(0.0, 0.6, 0.16, 0.4).dilate_pc(-0.1), "g", slot.ghost) {
+                slot.ghost = !slot.ghost;
+                coms.push(AudioCommand::SetSlotGhost(i, slot.ghost));
+            }
+            let r = r.child(0.18, 0.0, 0.82, 1.0);
+            let cols = 5 + NUM_BUSES as i32 - 1;
+            if slot.vol.frame(inputs, outputs, r.grid_child(0, 0, cols, 1)) {
+                coms.push(AudioCommand::SetSlotVol(i, db_to_vol(slot.vol.curr())));
+            }
+            if slot.pan.frame(inputs, outputs, r.grid_child(1, 0, cols, 1)) {
+                coms.push(AudioCommand::SetSlotPan(i, slot.pan.curr()));
+            }
+            for (j, knob) in [&mut slot.lo, &mut slot.hi].into_iter().enumerate() {
+                let rk = r.grid_child(2 + j as i32, 0, cols, 1);
+                knob.frame(inputs, outputs, rk);
+                note_label(outputs, rk, knob.curr().round() as i32);
+            }
+            let rt = r.grid_child(4, 0, cols, 1);
+            slot.tuning.frame(inputs, outputs, rt);
+            let name = self.tunings[slot.tuning_id()].as_ref().map_or("drop a scl", |t| t.name());
+            let w = 0.08 * rt.h;
+            outputs.glyphs.push_center_str(name, rt.x + rt.w/2.0, rt.y + rt.h - w, w, w, 1.2, v4(1.0, 1.0, 1.0, 1.0));
+            for b in 1..NUM_BUSES {
+                if slot.sends[b].frame(inputs, outputs, r.grid_child(4 + b as i32, 0, cols, 1)) {
+                    coms.push(AudioCommand::SetSend(i, b, slot.sends[b].curr()));
+                }
+            }
+        }
+
+        let r = r.grid_child(NUM_SLOTS as i32, 0, NUM_SLOTS as i32 + 1, 1).dilate_pc(-0.02);
+        outputs.canvas.put_rect(r, 1.01, Vec4::new(0.5, 0.1, 0.1, 1.0));
+        if self.aout.frame(inputs, outputs, r.grid_child(0, 0, 3, 1)) {
+            coms.push(AudioCommand::SetVol(db_to_vol(self.aout.curr())));
+        }
+        // pitch for the whole keyboard, the octave is on the arrow keys
+        self.a4.frame(inputs, outputs, r.grid_child(1, 0, 3, 1));
+        let rt = r.grid_child(2, 0, 3, 1);
+        self.transpose.frame(inputs, outputs, rt);
+        let w = 0.08 * rt.h;
+        let lowest = note_name(self.shift.key_to_midi(0));
+        outputs.glyphs.push_center_str(&format!("oct {} {}", self.shift.octave, lowest), rt.x + rt.w/2.0, rt.y + rt.h - w, w, w, 1.2, v4(1.0, 1.0, 1.0, 1.0));
+
+        for com in coms {
+            self.send(outputs, com);
+        }
+    }
+
+    fn rack_frame(&mut self, inputs: &FrameInputState, outputs: &mut FrameOutputs, r: Rect) {
+        let r = r.dilate_pc(-0.01);
+        outputs.canvas.put_rect(r, 1.01, Vec4::new(0.9, 0.2, 0.2, 1.0));
+        let title = format!("inserts {}", rack_name(self.rack_sel));
+        outputs.glyphs.push_center_str(&title, r.x + r.w/2.0, r.y + 0.1*r.h/2.0, 0.1*r.h/2.5, 0.1*r.h/2.5, 1.2, v4(1.0, 1.0, 1.0, 1.0));
+        let r = r.child(0.0, 0.1, 1.0, 0.9).dilate_pc(-0.01);
+
+        let mut coms = vec![];
+
+        // which chain, and the bus controls if its a bus
+        let (r_chains, r) = r.split_lr(0.07);
+        let n_racks = NUM_BUSES + 1;
+        for i in 0..n_racks {
+            let rb = r_chains.child(0.0, 0.0, 1.0, 0.5).grid_child(0, i as i32, 1, n_racks as i32).dilate_pc(-0.05);
+            let (rb, rm) = rb.split_lr(0.85);
+            if button(inputs, outputs, rb, rack_name(i), i == self.rack_sel) {
+                self.rack_sel = i;
+            }
+            let level = match rack_chain(i) {
+                ChainId::Master => self.local_mixer.master_meter.env,
+                ChainId::Bus(b) => self.local_mixer.bus_meters[b].env,
+            };
+            meter(outputs, rm.dilate_pc(-0.1), level);
+        }
+        if let ChainId::Bus(b) = rack_chain(self.rack_sel) {
+            let strip = &mut self.bus_strips[b];
+            let r = r_chains.child(0.0, 0.5, 1.0, 0.5);
+            if button(inputs, outputs, r.grid_child(0, 0, 2, 3).dilate_pc(-0.05), "m", strip.mute) {
+                strip.mute = !strip.mute;
+                coms.push(AudioCommand::SetBusMute(b, strip.mute));
+            }
+            if button(inputs, outputs, r.grid_child(1, 0, 2, 3).dilate_pc(-0.05), "s", strip.solo) {
+                strip.solo = !strip.solo;
+                coms.push(AudioCommand::SetBusSolo(b, strip.solo));
+            }
+            if strip.fader.frame(inputs, outputs, r.child(0.0, 1.0/3.0, 1.0, 2.0/3.0)) {
+                coms.push(AudioCommand::SetBusFader(b, db_to_vol(strip.fader.curr())));
+            }
+        }
+
+        let (r_add, r_slots) = r.split_lr(0.08);
+        for (i, kind) in EffectKind::ALL.iter().enumerate() {
+            let rb = r_add.grid_child(0, i as i32, 1, EffectKind::ALL.len() as i32).dilate_pc(-0.05);
+            if button(inputs, outputs, rb, kind.name(), false) {
+                self.add_effect(outputs, *kind);
+            }
+        }
+
+        let panel = self.delay_slot();
+        let rack = &mut self.racks[self.rack_sel];
+        let n = rack.len().max(5);
+        let mut remove = None;
+        let mut swap = None;
+        for (i, slot) in rack.iter_mut().enumerate() {
+            let r = r_slots.grid_child(i as i32, 0, n as i32, 1).dilate_pc(-0.02);
+            outputs.canvas.put_rect(r, 1.02, Vec4::new(0.5, 0.1, 0.1, 1.0));
+            outputs.glyphs.push_center_str(slot.name, r.x + r.w/2.0, r.y + 0.1*r.h/2.0, 0.1*r.h/2.5, 0.1*r.h/2.5, 1.2, v4(1.0, 1.0, 1.0, 1.0));
+
+            let rb = r.child(0.0, 0.1, 1.0, 0.1);
+            if button(inputs, outputs, rb.grid_child(0, 0, 4, 1).dilate_pc(-0.1), "byp", slot.bypass) {
+                slot.bypass = !slot.bypass;
+                coms.push(AudioCommand::BypassEffect(slot.id, slot.bypass));
+            }
+            if button(inputs, outputs, rb.grid_child(1, 0, 4, 1).dilate_pc(-0.1), "<", false) && i > 0 {
+                swap = Some((i, i - 1));
+            }
+            if button(inputs, outputs, rb.grid_child(2, 0, 4, 1).dilate_pc(-0.1), ">", false) {
+                swap = Some((i, i + 1));
+            }
+            if button(inputs, outputs, rb.grid_child(3, 0, 4, 1).dilate_pc(-0.1), "x", false) {
+                remove = Some(i);
+            }
+
+            let r = r.child(0.0, 0.2, 1.0, 0.8);
+            // drawing its knobs twice would turn them twice as fast
+            if panel == Some(i) {
+                let w = 0.1 * r.h / 2.5;
+                outputs.glyphs.push_center_str("in panel", r.x + r.w/2.0, r.y + (r.h - w)/2.0, w, w, 1.2, v4(1.0, 1.0, 1.0, 1.0));
+                continue;
+            }
+            let toggles = rack_toggles(slot.name);
+            let cols = ((slot.knobs.len() as f32).sqrt().ceil() as i32).max(2);
+            let rows = ((slot.knobs.len() as i32 + cols - 1) / cols).max(2);
+            for (j, knob) in slot.knobs.iter_mut().enumerate() {
+                let rk = r.grid_child(j as i32 % cols, j as i32 / cols, cols, rows);
+                let changed = if toggles.contains(&j) {
+                    toggle(inputs, outputs, rk.child(0.1, 0.3, 0.8, 0.4), knob)
+                } else {
+                    knob.frame(inputs, outputs, rk)
+                };
+                if changed {
+                    coms.push(AudioCommand::SetEffectParam(slot.id, j, knob.curr()));
+                }
+            }
+        }
+
+        if let Some((from, to)) = swap {
+            if to < rack.len() {
+                rack.swap(from, to);
+                coms.push(AudioCommand::MoveEffect(rack[to].id, to));
+            }
+        }
+        if let Some(i) = remove {
+            let slot = rack.remove(i);
+            coms.push(AudioCommand::RemoveEffect(slot.id));
+        }
+        for com in coms {
+            self.send(outputs, com);
+        }
+    }
+
+    // first delay on the chain the rack is showing, thats the one the delay panel edits
+    fn delay_slot(&self) -> Option<usize> {
+        self.racks[self.rack_sel].iter().position(|s| s.name == "delay")
+    }
+
+    fn delay_frame(&mut self, inputs: &FrameInputState, outputs: &mut FrameOutputs, r: Rect) {
+        let r = r.dilate_pc(-0.01);
+        outputs.canvas.put_rect(r, 1.01, Vec4::new(0.9, 0.2, 0.2, 1.0));
+        let title = format!("delay {}", rack_name(self.rack_sel));
+        outputs.glyphs.push_center_str(&title, r.x + r.w/2.0, r.y + 0.1*r.h/2.0, 0.1*r.h/2.5, 0.1*r.h/2.5, 1.2, v4(1.0, 1.0, 1.0, 1.0));
+        let r = r.child(0.0, 0.1, 1.0, 0.9).dilate_pc(-0.01);
+
+        let i = match self.delay_slot() {
+            Some(i) => i,
+            None => {
+                if button(inputs, outputs, r.child(0.3, 0.4, 0.4, 0.2), "add delay", false) {
+                    self.add_effect(outputs, EffectKind::Delay);
+                }
+                return;
+            },
+        };
+        let slot = &mut self.racks[self.rack_sel][i];
+        let mut coms = vec![];
+
+        let knobs = [
+            (delay::TIME, 0, 0), (delay::DIV, 1, 0), (delay::FEEDBACK, 2, 0), (delay::MIX, 3, 0),
+            (delay::HIGH_CUT, 0, 1), (delay::LOW_CUT, 1, 1),
+        ];
+        for (p, x, y) in knobs {
+            if slot.knobs[p].frame(inputs, outputs, r.grid_child(x, y, 4, 2)) {
+                coms.push(AudioCommand::SetEffectParam(slot.id, p, slot.knobs[p].curr()));
+            }
+        }
+        for (p, x) in [(delay::SYNC, 2), (delay::PING_PONG, 3)] {
+            if toggle(inputs, outputs, r.grid_child(x, 1, 4, 2).child(0.1, 0.3, 0.8, 0.4), &mut slot.knobs[p]) {
+                coms.push(AudioCommand::SetEffectParam(slot.id, p, slot.knobs[p].curr()));
+            }
+        }
+
+        // whichever of time or division is in charge gets its value under it
+        let synced = slot.knobs[delay::SYNC].curr() > 0.5;
+        let (rl, text) = if synced {
+            (r.grid_child(1, 0, 4, 2), delay::DIVISION_NAMES[slot.knobs[delay::DIV].curr().round() as usize].to_string())
+        } else {
+            (r.grid_child(0, 0, 4, 2), format!("{:.0}ms", slot.knobs[delay::TIME].curr()))
+        };
+        let w = 0.08 * rl.h;
+        outputs.glyphs.push_center_str(&text, rl.x + rl.w/2.0, rl.y + rl.h - w, w, w, 1.2, v4(1.0, 1.0, 1.0, 1.0));
+
+        for com in coms {
+            self.send(outputs, com);
+        }
+    }
+
+    // Draws the response of the first eq in the rack into the fft texture and lets you drag the bands around.
+    // The texture goes over the top of the canvas so the handles are glyphs
+    fn eq_frame(&mut self, inputs: &FrameInputState, outputs: &mut FrameOutputs, r: Rect, tb: &mut TextureBuffer) {
+        let slot = match self.racks[0].iter_mut().find(|s| s.name == "eq") {
+            Some(slot) => slot,
+            None => {
+                self.eq_drag = None;
+                return;
+            },
+        };
+        let id = slot.id;
+
+        let db_to_y = |db: f32| 0.5 - db / (2.0 * EQ_DISPLAY_DB);
+        let mut coms = vec![];
+
+        // dragging
+        if inputs.lmb == KeyStatus::JustPressed && r.contains(inputs.mouse_pos) {
+            let p = r.relative_point(inputs.mouse_pos);
+            self.eq_drag = (0..EQ_BANDS).find(|&b| {
+                let hx = freq_to_x(slot.knobs[b * 3].curr());
+                let hy = db_to_y(slot.knobs[b * 3 + 1].curr());
+                (hx - p.x).abs() * r.w < 0.01 && (hy - p.y).abs() * r.h < 0.01
+            });
+        }
+        if inputs.lmb != KeyStatus::Pressed && inputs.lmb != KeyStatus::JustPressed {
+            self.eq_drag = None;
+        }
+        if let Some(b) = self.eq_drag {
+            let p = r.relative_point(inputs.mouse_pos);
+            slot.knobs[b * 3].set_val(x_to_freq(p.x.clamp(0.0, 1.0)));
+            slot.knobs[b * 3 + 1].set_val((0.5 - p.y) * 2.0 * EQ_DISPLAY_DB);
+            coms.push(AudioCommand::SetEffectParam(id, b * 3, slot.knobs[b * 3].curr()));
+            coms.push(AudioCommand::SetEffectParam(id, b * 3 + 1, slot.knobs[b * 3 + 1].curr()));
+        }
+
+        // scroll on a handle for q
+        if inputs.scroll_delta != 0.0 && r.contains(inputs.mouse_pos) {
+            let p = r.relative_point(inputs.mouse_pos);
+            let nearest = (0..EQ_BANDS).min_by(|&a, &b| {
+                let da = (freq_to_x(slot.knobs[a * 3].curr()) - p.x).abs();
+                let db = (freq_to_x(slot.knobs[b * 3].curr()) - p.x).abs();
+                da.partial_cmp(&db).unwrap()
+            }).unwrap();
+            let q = slot.knobs[nearest * 3 + 2].curr() * if inputs.scroll_delta > 0.0 { 1.1 } else { 1.0 / 1.1 };
+            slot.knobs[nearest * 3 + 2].set_val(q);
+            coms.push(AudioCommand::SetEffectParam(id, nearest * 3 + 2, slot.knobs[nearest * 3 + 2].curr()));
+        }
+
+        // curve, joined up vertically so steep bits dont break
+        let params: Vec<f32> = slot.knobs.iter().map(|k| k.curr()).collect();
+        let c_curve = v4(1.0, 1.0, 0.0, 1.0);
+        let mut prev_y = None;
+        for i in 0..tb.w {
+            let db = eq_response_db(&params, x_to_freq(i as f32 / tb.w as f32));
+            let y = ((db_to_y(db) * tb.h as f32) as i32).clamp(0, tb.h as i32 - 1);
+            let (lo, hi) = match prev_y {
+                Some(py) => (y.min(py), y.max(py)),
+                None => (y, y),
+            };
+            for j in lo..=hi {
+                tb.set(i as i32, j, c_curve);
+            }
+            prev_y = Some(y);
+        }
+
+        // handles
+        let labels = ["L", "1", "2", "3", "H"];
+        for b in 0..EQ_BANDS {
+            let hx = r.x + freq_to_x(params[b * 3]) * r.w;
+            let hy = r.y + db_to_y(params[b * 3 + 1]) * r.h;
+            let c = if self.eq_drag == Some(b) { v4(1.0, 0.0, 0.0, 1.0) } else { c_curve };
+            outputs.glyphs.push_center_str(labels[b], hx, hy - 0.01, 0.02, 0.02, 2.5, c);
+        }
+
+        for com in coms {
+            self.send(outputs, com);
+        }
+    }
+
+    // keyframe editor for the selected slot when its on the morph voice. one field at a time,
+    // click to add or drag, right click to remove, scroll for the curve into a key
+    fn morph_frame(&mut self, inputs: &FrameInputState, outputs: &mut FrameOutputs, r: Rect) {
+        let r = r.dilate_pc(-0.01);
+        outputs.canvas.put_rect(r, 1.01, Vec4::new(0.9, 0.2, 0.2, 1.0));
+        let r = r.dilate_pc(-0.01);
+        let (r_fields, r_plot) = r.split_ud(0.15);
+
+        for (i, (name, _, _)) in FRAME_FIELDS.iter().enumerate() {
+            let rb = r_fields.grid_child(i as i32, 0, FRAME_FIELDS.len() as i32, 1).dilate_pc(-0.05);
+            if button(inputs, outputs, rb, name, i == self.morph_field) {
+                self.morph_field = i;
+            }
+        }
+
+        let field = self.morph_field;
+        let (_, min, max) = FRAME_FIELDS[field];
+        let desc = &mut self.slots[self.selected].knobs.morph;
+
+        let val_to_y = |v: f32| 1.0 - (v - min) / (max - min);
+        let y_to_val = |y: f32| min + (1.0 - y) * (max - min);
+        let held_x = |t: f32| (t / MORPH_DISPLAY_S).min(1.0) * MORPH_SPLIT;
+        let release_x = |t: f32| MORPH_SPLIT + (t / MORPH_RELEASE_S).min(1.0) * (1.0 - MORPH_SPLIT);
+        let handle = |i: usize, desc: &MorphDesc| {
+            if i == MAX_KEYFRAMES {
+                v2(release_x(desc.release.time), val_to_y(desc.release.frame.get(field)))
+            } else {
+                v2(held_x(desc.keys[i].time), val_to_y(desc.keys[i].frame.get(field)))
+            }
+        };
+        let near = |p: Vec2, q: Vec2| (p.x - q.x).abs() * r_plot.w < 0.01 && (p.y - q.y).abs() * r_plot.h < 0.01;
+
+        if r_plot.contains(inputs.mouse_pos) {
+            let p = r_plot.relative_point(inputs.mouse_pos);
+            let hit = (0..desc.len).chain(std::iter::once(MAX_KEYFRAMES)).find(|&i| near(p, handle(i, desc)));
+
+            if inputs.lmb == KeyStatus::JustPressed {
+                self.morph_drag = match hit {
+                    Some(i) => Some(i),
+                    None if p.x < MORPH_SPLIT => {
+                        let t = p.x / MORPH_SPLIT * MORPH_DISPLAY_S;
+                        let mut frame = desc.frame_at(t);
+                        frame.set(field, y_to_val(p.y));
+                        desc.insert(Keyframe { time: t, curve: 1.0, frame })
+                    },
+                    None => None,
+                };
+            }
+            if inputs.rmb == KeyStatus::JustPressed {
+                if let Some(i) = hit.filter(|&i| i < MAX_KEYFRAMES) {
+                    desc.remove(i);
+                    self.morph_drag = None;
+                }
+            }
+            if inputs.scroll_delta != 0.0 {
+                if let Some(i) = hit {
+                    let key = if i == MAX_KEYFRAMES { &mut desc.release } else { &mut desc.keys[i] };
+                    let c = key.curve * if inputs.scroll_delta > 0.0 { 1.1 } else { 1.0 / 1.1 };
+                    key.curve = c.clamp(0.1, 10.0);
+                }
+            }
+        }
+        if inputs.lmb != KeyStatus::Pressed && inputs.lmb != KeyStatus::JustPressed {
+            self.morph_drag = None;
+        }
+        if let Some(i) = self.morph_drag {
+            let p = r_plot.relative_point(inputs.mouse_pos);
+            let val = y_to_val(p.y.clamp(0.0, 1.0));
+            if i == MAX_KEYFRAMES {
+                desc.release.time = ((p.x - MORPH_SPLIT) / (1.0 - MORPH_SPLIT)).clamp(0.0, 1.0) * MORPH_RELEASE_S;
+                desc.release.frame.set(field, val);
+            } else if i < desc.len {
+                desc.set_time(i, (p.x / MORPH_SPLIT).clamp(0.0, 1.0) * MORPH_DISPLAY_S);
+                desc.keys[i].frame.set(field, val);
+            }
+        }
+
+        // plot, sampled along the held part and then released from the last key
+        outputs.canvas.put_rect(r_plot, 1.02, v4(0.0, 0.0, 0.0, 1.0));
+        let split_x = r_plot.x + MORPH_SPLIT * r_plot.w;
+        outputs.canvas.put_rect(Rect::new(split_x, r_plot.y, 0.002, r_plot.h), 1.03, v4(0.4, 0.4, 0.4, 1.0));
+        let to_screen = |x: f32, v: f32| v2(r_plot.x + x * r_plot.w, r_plot.y + val_to_y(v) * r_plot.h);
+        let c_curve = v4(1.0, 1.0, 0.0, 1.0);
+        let n = 100;
+        let mut prev = to_screen(0.0, desc.frame_at(0.0).get(field));
+        for i in 1..=n {
+            let t = i as f32 / n as f32 * MORPH_DISPLAY_S;
+            let q = to_screen(held_x(t), desc.frame_at(t).get(field));
+            outputs.canvas.put_line(prev, q, 0.002, 1.03, c_curve);
+            prev = q;
+        }
+        let released_at = desc.keys()[desc.len - 1].time;
+        let mut prev = to_screen(MORPH_SPLIT, desc.frame_at(released_at).get(field));
+        for i in 1..=n {
+            let t = i as f32 / n as f32 * MORPH_RELEASE_S;
+            let q = to_screen(release_x(t), desc.frame_released(released_at + t, released_at).get(field));
+            outputs.canvas.put_line(prev, q, 0.002, 1.03, c_curve);
+            prev = q;
+        }
+
+        for i in (0..desc.len).chain(std::iter::once(MAX_KEYFRAMES)) {
+            let h = handle(i, desc);
+            let c = if self.morph_drag == Some(i) { v4(1.0, 0.0, 0.0, 1.0) } else { v4(1.0, 1.0, 1.0, 1.0) };
+            let p = v2(r_plot.x + h.x * r_plot.w, r_plot.y + h.y * r_plot.h);
+            outputs.canvas.put_rect(p.rect_centered(0.01, 0.01), 1.04, c);
+        }
+    }
+
+    // these voices end themselves, the release is just in case
+    fn one_shot(&mut self, outputs: &mut FrameOutputs, sd: SoundDesc, dur: f32) {
+        outputs.play_for(self.selected, sd, dur);
+    }
+
+    fn hit_pad(&mut self, outputs: &mut FrameOutputs, pad: usize) {
+        let sd = self.pads[pad].get_sd(&self.slots[self.selected].knobs, self.a4.curr());
+        self.one_shot(outputs, sd, self.pads[pad].decay.curr());
+    }
+
+    fn play_sfx(&mut self, outputs: &mut FrameOutputs) {
+        let knobs = &self.slots[self.selected].knobs;
+        let mut sd = knobs.get_sd(midi_to_freq(60.0, self.a4.curr()), self.a4.curr());
+        sd.voice = VoiceKind::ALL.iter().position(|k| *k == VoiceKind::Sfx).unwrap() as f32;
+        self.one_shot(outputs, sd, SFX_PREVIEW_SECS);
+    }
+
+    // category buttons roll a new patch, unlocked params only. mutate nudges the current one
+    fn sfx_frame(&mut self, inputs: &FrameInputState, outputs: &mut FrameOutputs, r: Rect) {
+        let r = r.dilate_pc(-0.01);
+        outputs.canvas.put_rect(r, 1.01, Vec4::new(0.9, 0.2, 0.2, 1.0));
+        let r = r.dilate_pc(-0.01);
+        let (r_buttons, r_params) = r.split_ud(0.15);
+
+        let n_buttons = SfxCategory::ALL.len() as i32 + 3;
+        let rb = |i: i32| r_buttons.grid_child(i, 0, n_buttons, 1).dilate_pc(-0.05);
+        let current = self.slots[self.selected].knobs.get_sd(0.0, self.a4.curr()).sfx;
+        let mut new_params = None;
+        for (i, cat) in SfxCategory::ALL.iter().enumerate() {
+            if button(inputs, outputs, rb(i as i32), cat.name(), i == self.sfx_cat) {
+                self.sfx_cat = i;
+                self.sfx_seed = inputs.seed;
+                new_params = Some(generate(*cat, inputs.seed, &current, &self.sfx_locks));
+            }
+        }
+        let n = SfxCategory::ALL.len() as i32;
+        if button(inputs, outputs, rb(n), "mutate", false) {
+            new_params = Some(mutate(&current, inputs.seed, &self.sfx_locks));
+        }
+        let mut play = button(inputs, outputs, rb(n + 1), "play", false);
+        if button(inputs, outputs, rb(n + 2), "save", false) {
+            let name = format!("{}_{}", SfxCategory::ALL[self.sfx_cat].name(), self.sfx_seed);
+            let path = Path::new(PATCH_DIR).join(name).with_extension("patch");
+            match self.slots[self.selected].knobs.build_patch().save(&path) {
+                Ok(()) => println!("saved {}", path.display()),
+                Err(e) => println!("couldnt save: {}", e),
+            }
+        }
+        if let Some(p) = new_params {
+            self.slots[self.selected].knobs.set_sfx(&p);
+            play = true;
+        }
+
+        let knobs = &mut self.slots[self.selected].knobs;
+        let cols = 7;
+        let rows = (SFX_PARAMS as i32 + cols - 1) / cols;
+        for (i, knob) in knobs.sfx.iter_mut().enumerate() {
+            let rc = r_params.grid_child(i as i32 % cols, i as i32 / cols, cols, rows);
+            knob.frame(inputs, outputs, rc.child(0.0, 0.0, 0.75, 1.0));
+            if button(inputs, outputs, rc.child(0.75, 0.0, 0.25, 0.3).dilate_pc(-0.1), "l", self.sfx_locks[i]) {
+                self.sfx_locks[i] = !self.sfx_locks[i];
+            }
+        }
+
+        if play {
+            self.play_sfx(outputs);
+        }
+    }
+
+    fn pads_frame(&mut self, inputs: &FrameInputState, outputs: &mut FrameOutputs, r: Rect) {
+        let r = r.dilate_pc(-0.01);
+        let mut hits = vec![];
+        let n = self.pads.len() as i32;
+        for (i, pad) in self.pads.iter_mut().enumerate() {
+            let r = r.grid_child(i as i32, 0, n, 1).dilate_pc(-0.02);
+            outputs.canvas.put_rect(r, 1.01, Vec4::new(0.5, 0.1, 0.1, 1.0));
+            let held = inputs.key_held(pad.key);
+            if button(inputs, outputs, r.child(0.0, 0.0, 0.2, 1.0).dilate_pc(-0.1), pad.kind.name(), held) || inputs.key_rising(pad.key) {
+                hits.push(i);
+            }
+            let r = r.child(0.22, 0.0, 0.78, 1.0);
+            pad.tune.frame(inputs, outputs, r.grid_child(0, 0, 4, 1));
+            pad.decay.frame(inputs, outputs, r.grid_child(1, 0, 4, 1));
+            pad.tone.frame(inputs, outputs, r.grid_child(2, 0, 4, 1));
+            pad.punch.frame(inputs, outputs, r.grid_child(3, 0, 4, 1));
+        }
+        for i in hits {
+            self.hit_pad(outputs, i);
+        }
+    }
+
+    fn arp_frame(&mut self, inputs: &FrameInputState, outputs: &mut FrameOutputs, r: Rect) {
+        let r = r.dilate_pc(-0.01);
+        outputs.canvas.put_rect(r, 1.01, Vec4::new(0.5, 0.1, 0.1, 1.0));
+        let p = &mut self.arp_panel;
+        let (rb, r) = r.split_lr(0.16);
+        if button(inputs, outputs, rb.child(0.0, 0.0, 1.0, 0.5).dilate_pc(-0.1), "arp", p.on) {
+            p.on = !p.on;
+            // notes held going in keep playing normally, the arp starts empty
+            self.arp_keys.clear();
+            self.arp_pending.clear();
+            self.arp.clear();
+        }
+        if button(inputs, outputs, rb.child(0.0, 0.5, 1.0, 0.5).dilate_pc(-0.1), "latch", p.latch) {
+            p.latch = !p.latch;
+        }
+        let knobs = [&mut p.mode, &mut p.octaves, &mut p.rate, &mut p.gate, &mut p.swing];
+        let n = knobs.len() as i32;
+        for (i, knob) in knobs.into_iter().enumerate() {
+            knob.frame(inputs, outputs, r.grid_child(i as i32, 0, n, 1));
+        }
+        let rm = r.grid_child(0, 0, n, 1);
+        let w = 0.12 * rm.h;
+        outputs.glyphs.push_center_str(ArpMode::from_param(p.mode.curr()).name(), rm.x + rm.w/2.0, rm.y + rm.h - w, w, w, 1.4, v4(1.0, 1.0, 1.0, 1.0));
+    }
+
+    fn toggle_play(&mut self, inputs: &FrameInputState, outputs: &mut FrameOutputs) {
+        if self.transport.playing {
+            self.transport.stop();
+            self.seq.stop();
+            // steps that never started dont reach the arp, ones that did get let go
+            let mut unstarted = vec![];
+            for (_, note, down) in std::mem::take(&mut self.arp_pending) {
+                if down {
+                    unstarted.push(note);
+                } else if let Some(i) = unstarted.iter().position(|n| *n == note) {
+                    unstarted.swap_remove(i);
+                } else {
+                    self.arp.note_off(note);
+                }
+            }
+            // anything the transport scheduled inside the lookahead goes too: steps still
+            // waiting to start never do, tied ones already going get let go, clicks are dropped
+            self.send(outputs, AudioCommand::Cancel(GROUP_SEQ));
+            self.send(outputs, AudioCommand::Cancel(GROUP_CLICK));
+            for (h, _) in std::mem::take(&mut self.seq_voices) {
+                outputs.release(h);
+            }
+        } else {
+            self.transport.play(inputs.clock);
+            self.seq.start(inputs.seed);
+            self.last_tick = None;
+        }
+    }
+
+    fn transport_frame(&mut self, inputs: &FrameInputState, outputs: &mut FrameOutputs, r: Rect) {
+        let r = r.dilate_pc(-0.01);
+        outputs.canvas.put_rect(r, 1.01, Vec4::new(0.5, 0.1, 0.1, 1.0));
+        let (rb, r) = r.split_lr(0.3);
+        let b = |x: i32, y: i32| rb.grid_child(x, y, 2, 2).dilate_pc(-0.1);
+        if button(inputs, outputs, b(0, 0), "play", self.transport.playing) || inputs.key_rising(PLAY_KEY) {
+            self.toggle_play(inputs, outputs);
+        }
+        if button(inputs, outputs, b(1, 0), "loop", self.transport.looping) {
+            self.transport.looping = !self.transport.looping;
+        }
+        if button(inputs, outputs, b(0, 1), "click", self.transport_panel.metronome) {
+            self.transport_panel.metronome = !self.transport_panel.metronome;
+        }
+        let (bar, beat) = self.transport.bar_beat(inputs.clock);
+        let rp = b(1, 1);
+        let w = rp.h * 0.6;
+        outputs.glyphs.push_center_str(&format!("{}.{}", bar, beat), rp.x + rp.w/2.0, rp.y + rp.h/2.0, w, w, 1.3, v4(1.0, 1.0, 1.0, 1.0));
+
+        let p = &mut self.transport_panel;
+        let knobs = [&mut p.bpm, &mut p.beats, &mut p.unit, &mut p.loop_start, &mut p.loop_len];
+        let n = knobs.len() as i32;
+        for (i, knob) in knobs.into_iter().enumerate() {
+            knob.frame(inputs, outputs, r.grid_child(i as i32, 0, n, 1));
+        }
+        let rs = r.grid_child(2, 0, n, 1);
+        let w = 0.12 * rs.h;
+        let sig = format!("{}/{}", p.beats.curr().round(), BEAT_UNITS[p.unit.curr().round() as usize]);
+        outputs.glyphs.push_center_str(&sig, rs.x + rs.w/2.0, rs.y + rs.h - w, w, w, 1.4, v4(1.0, 1.0, 1.0, 1.0));
+    }
+
+    // a slide step glides the voices the last step left hanging, anything else lets them go
+    fn play_step(&mut self, inputs: &FrameInputState, outputs: &mut FrameOutputs, sn: SeqNote, step_len: f64) {
+        let step = self.seq.patterns[sn.pattern].steps[sn.step].clone();
+        let to_t = |s: u64| inputs.t + (s as f32 - inputs.clock as f32) / SAMPLE_RATE;
+        let end = sn.off.unwrap_or(sn.on + step_len as u64);
+
+        // with the arp on the steps are what it arpeggiates, it gets them when they start and end
+        if self.arp_panel.on {
+            for (h, _) in std::mem::take(&mut self.seq_voices) {
+                self.send(outputs, AudioCommand::At(sn.on, GROUP_SEQ, Timed::Release(h.0)));
+            }
+            self.arp_pending.push((sn.on, step.note, true));
+            self.arp_pending.push((end, step.note, false));
+            return;
+        }
+        self.history.push(PlayedNote { note: step.note, start: to_t(sn.on), end: to_t(end), vel: step.vel, src: NoteSource::Seq });
+
+        let sounds = self.slot_sounds(step.note, step.vel, Some((step.lock_slot, &step.locks)));
+        let mut coms = vec![];
+        if step.slide && !self.seq_voices.is_empty() {
+            for (h, i) in self.seq_voices.iter() {
+                if let Some((_, sd)) = sounds.iter().find(|(j, _)| j == i) {
+                    coms.push(AudioCommand::At(sn.on, GROUP_SEQ, Timed::Slide(h.0, sd.f)));
+                }
+            }
+        } else {
+            for (h, _) in self.seq_voices.drain(..) {
+                coms.push(AudioCommand::At(sn.on, GROUP_SEQ, Timed::Release(h.0)));
+            }
+            for (i, sd) in sounds {
+                let h = VoiceHandle::next();
+                coms.push(AudioCommand::At(sn.on, GROUP_SEQ, Timed::PlayHold(h.0, i, Box::new(sd))));
+                self.seq_voices.push((h, i));
+            }
+        }
+        if let Some(off) = sn.off {
+            for (h, _) in self.seq_voices.drain(..) {
+                coms.push(AudioCommand::At(off, GROUP_SEQ, Timed::Release(h.0)));
+            }
+        }
+        for com in coms {
+            self.send(outputs, com);
+        }
+    }
+
+    fn seq_frame(&mut self, inputs: &FrameInputState, outputs: &mut FrameOutputs, r_ctl: Rect, r: Rect) {
+        let ctl = |x: i32, y: i32| r_ctl.grid_child(x, y, 2, 7).dilate_pc(-0.05);
+        let white = v4(1.0, 1.0, 1.0, 1.0);
+
+        if button(inputs, outputs, ctl(0, 0), "play", self.seq.playing) {
+            self.toggle_play(inputs, outputs);
+        }
+        let len = self.seq.patterns[self.seq.edit].len;
+        if button(inputs, outputs, ctl(1, 0), &format!("{}", len), false) {
+            self.seq.patterns[self.seq.edit].len = if len == 16 { MAX_STEPS } else { 16 };
+        }
+        // which pattern the grid edits
+        for p in 0..NUM_PATTERNS {
+            if button(inputs, outputs, ctl(p as i32 % 2, 1 + p as i32 / 2), &format!("{}", p + 1), p == self.seq.edit) {
+                self.seq.edit = p;
+                self.seq_lock = None;
+            }
+        }
+        if button(inputs, outputs, ctl(0, 5), "chain", false) {
+            self.seq.chain.push(self.seq.edit);
+        }
+        if button(inputs, outputs, ctl(1, 5), "unchain", false) {
+            self.seq.chain.clear();
+        }
+        if let Some(i) = self.seq_lock {
+            if button(inputs, outputs, ctl(0, 6), "unlock", false) {
+                self.seq.patterns[self.seq.edit].steps[i].locks = Patch::default();
+            }
+        }
+
+        // chain along the top of the grid
+        let (r_top, r) = r.split_ud(0.08);
+        let chain: Vec<String> = self.seq.chain.iter().map(|p| format!("{}", p + 1)).collect();
+        let w = r_top.h * 0.6;
+        let label = if chain.is_empty() { "looping".to_string() } else { format!("chain {}", chain.join(" ")) };
+        outputs.glyphs.push_str(&label, r_top.x + w, r_top.y + r_top.h * 0.2, w, w, 1.3, white);
+
+        // note grid on top, per step lanes underneath
+        const ROWS: i32 = 24;
+        const LANES: i32 = 5;
+        let (r_names, r) = r.split_lr(0.03);
+        let (r_grid, r_lanes) = r.split_ud(0.7);
+        if r_grid.contains(inputs.mouse_pos) {
+            if inputs.scroll_delta > 0.0 {
+                self.seq_low = (self.seq_low + 12).min(127 - ROWS + 1);
+            } else if inputs.scroll_delta < 0.0 {
+                self.seq_low = (self.seq_low - 12).max(0);
+            }
+        }
+        for row in 0..ROWS {
+            let note = self.seq_low + row;
+            if pitch_class(note) == 0 {
+                let rn = r_names.child(0.0, 0.0, 1.0, 0.7).grid_child(0, ROWS - 1 - row, 1, ROWS);
+                let w = rn.h.min(rn.w / 3.0);
+                outputs.glyphs.push_center_str(&note_name(note), rn.x + rn.w/2.0, rn.y + rn.h/2.0, w, w, 1.2, white);
+            }
+        }
+
+        let (play_pattern, play_step) = self.seq.locate((self.transport.beat(inputs.clock) * self.seq.rate as f64) as u64);
+        let lane_names = ["vel", "gate", "prob", "slide", "lock"];
+        for (l, name) in lane_names.iter().enumerate() {
+            let rn = r_names.child(0.0, 0.7, 1.0, 0.3).grid_child(0, l as i32, 1, LANES);
+            let w = rn.h.min(rn.w / 4.0) * 0.6;
+            outputs.glyphs.push_center_str(name, rn.x + rn.w/2.0, rn.y + rn.h/2.0, w, w, 1.2, white);
+        }
+
+        let pressed = inputs.lmb == KeyStatus::JustPressed;
+        let held = inputs.lmb == KeyStatus::Pressed || pressed;
+        let pattern = &mut self.seq.patterns[self.seq.edit];
+        for i in 0..len {
+            let col = r_grid.grid_child(i as i32, 0, len as i32, 1);
+            let step = &mut pattern.steps[i];
+            let playing = self.seq.playing && play_pattern == self.seq.edit && play_step == i;
+            let beat = if i % 4 == 0 { 0.18 } else { 0.12 };
+            outputs.canvas.put_rect(col.dilate_pc(-0.02), 1.1, Vec4::grey(if playing { 0.35 } else { beat }));
+
+            for row in 0..ROWS {
+                let note = self.seq_low + row;
+                let rc = col.grid_child(0, ROWS - 1 - row, 1, ROWS).dilate_pc(-0.05);
+                if step.on && step.note == note {
+                    let c = Vec4::new(72.0 * octave(note) as f32, 1.0 - pitch_class(note) as f32 / 16.0, 1.0, 1.0).hsv_to_rgb();
+                    outputs.canvas.put_rect(rc, 1.2, c);
+                }
+                if pressed && rc.contains(inputs.mouse_pos) {
+                    if step.on && step.note == note {
+                        step.on = false;
+                    } else {
+                        step.on = true;
+                        step.note = note;
+                    }
+                }
+            }
+
+            let lane = |l: i32| r_lanes.grid_child(i as i32, l, len as i32, LANES).dilate_pc(-0.05);
+            // bars you drag
+            for (l, v) in [&mut step.vel, &mut step.gate, &mut step.prob].into_iter().enumerate() {
+                let rl = lane(l as i32);
+                if held && rl.contains(inputs.mouse_pos) {
+                    *v = (1.0 - (inputs.mouse_pos.y - rl.y) / rl.h).clamp(0.0, 1.0);
+                }
+                outputs.canvas.put_rect(rl, 1.2, Vec4::grey(0.25));
+                outputs.canvas.put_rect(rl.child(0.0, 1.0 - *v, 1.0, *v), 1.3, v4(0.9, 0.2, 0.2, 1.0));
+            }
+            if button(inputs, outputs, lane(3), "", step.slide) {
+                step.slide = !step.slide;
+            }
+            let locked = !step.locks.values.is_empty();
+            let picked = self.seq_lock == Some(i);
+            if button(inputs, outputs, lane(4), if locked { "l" } else { "" }, picked) {
+                self.seq_lock = if picked { None } else { Some(i) };
+            }
+        }
+    }
+
+    // what each slot that covers this note would play, locks go over their slots knobs
+    fn slot_sounds(&self, note: i32, vel: f32, locks: Option<(usize, &Patch)>) -> Vec<(usize, SoundDesc)> {
+        let a4 = self.a4.curr();
+        let mut out = vec![];
+        for (i, slot) in self.slots.iter().enumerate() {
+            if !slot.plays(note) {
+                continue;
+            }
+            // keys the tunings mapping leaves out are silent
+            let tuning = self.tunings[slot.tuning_id()].as_ref();
+            let f = match tuning.and_then(|t| t.freq(note + slot.knobs.coarse.curr().round() as i32, a4)) {
+                Some(f) => f,
+                None => continue,
+            };
+            let mut sd = match locks {
+                Some((lock_slot, locks)) if lock_slot == i && !locks.values.is_empty() => {
+                    let mut knobs = slot.knobs.clone();
+                    knobs.apply_patch(locks);
+                    knobs.get_sd(f, a4)
+                },
+                _ => slot.knobs.get_sd(f, a4),
+            };
+            sd.vel = vel;
+            out.push((i, sd));
+        }
+        out
+    }
+
+    // tuning 0 stays 12-TET, so dropping onto it points the slot at a free one instead
+    fn tuning_target(&mut self) -> usize {
+        let slot = &mut self.slots[self.selected];
+        let id = slot.tuning_id();
+        if id != 0 {
+            return id;
+        }
+        let id = self.tunings.iter().position(|t| t.is_none()).unwrap_or(NUM_TUNINGS - 1);
+        slot.tuning.set_val(id as f32);
+        id
+    }
+
+    // dropped wavs go into whichever sample the selected slot is pointing at,
+    // dropped patches replace the selected slots knobs, scl and kbm files go into its tuning
+    fn load_dropped(&mut self, outputs: &mut FrameOutputs, path: &Path) {
+        let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        match ext.as_str() {
+            "patch" => {
+                match Patch::load(path) {
+                    Ok(patch) => self.slots[self.selected].knobs.apply_patch(&patch),
+                    Err(e) => println!("couldnt load {}: {}", path.display(), e),
+                }
+                return;
+            },
+            // a new scale keeps whatever mapping was there
+            "scl" => {
+                match load_scl(path) {
+                    Ok(scale) => {
+                        let id = self.tuning_target();
+                        let keymap = self.tunings[id].take().and_then(|t| t.keymap);
+                        self.tunings[id] = Some(Tuning { scale, keymap });
+                    },
+                    Err(e) => println!("couldnt load {}: {}", path.display(), e),
+                }
+                return;
+            },
+            // new layouts go on the end and get picked straight away
+            "keymap" => {
+                match KeyLayout::load(path) {
+                    Ok(layout) => {
+                        self.layouts.push(layout);
+                        self.layout = self.layouts.len() - 1;
+                    },
+                    Err(e) => println!("couldnt load {}: {}", path.display(), e),
+                }
+                return;
+            },
+            "kbm" => {
+                match load_kbm(path) {
+                    Ok(keymap) => {
+                        let scale = self.tunings[self.slots[self.selected].tuning_id()].as_ref().map_or(Scale::equal(12), |t| t.scale.clone());
+                        let id = self.tuning_target();
+                        self.tunings[id] = Some(Tuning { scale, keymap: Some(keymap) });
+                    },
+                    Err(e) => println!("couldnt load {}: {}", path.display(), e),
+                }
+                return;
+            },
+            _ => {},
+        }
+        let id = self.slots[self.selected].knobs.smp_id.curr().round() as usize;
+        match load_wav(path) {
+            Ok(wav) => {
+                let name = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+                self.sample_names[id] = Some(name.clone());
+                self.send(outputs, AudioCommand::LoadSample(id, Arc::new(Sample::from_wav(&name, wav))));
+            },
+            Err(e) => println!("couldnt load {}: {}", path.display(), e),
+        }
     }
-}
 
-impl SynthGUI {
     pub fn frame(&mut self, inputs: &FrameInputState, outputs: &mut FrameOutputs) {
+        let first_sound = outputs.sounds.len();
+        for path in inputs.dropped_files.iter() {
+            self.load_dropped(outputs, path);
+        }
+
+        let old_shift = self.shift;
+        if inputs.key_rising(OCTAVE_UP) {
+            self.shift.octave_up();
+        }
+        if inputs.key_rising(OCTAVE_DOWN) {
+            self.shift.octave_down();
+        }
+        if inputs.key_rising(NEXT_LAYOUT) {
+            self.layout = (self.layout + 1) % self.layouts.len();
+        }
+        self.shift.transpose = self.transpose.curr().round() as i32;
+        if self.shift.key_to_midi(0) != old_shift.key_to_midi(0) {
+            self.retune_held(outputs);
+        }
+
         // key presses
         let pressed_keys = inputs.curr_keys.difference(&inputs.prev_keys);
         for k in pressed_keys {
-            if let Some(note) = kc_to_note(*k) {
-                let uid = (31249577 + self.times_pressed.get(k).unwrap_or(&0)) * khash(12312577 * note as u32);
-                let f = self.knobs.base_freq.curr() * 2.0f32.powf(note as f32/12.0);
-                let sd = self.knobs.get_sd(f);
-                self.held_keys.insert(uid, (note, inputs.t, sd));
-
-                let com = AudioCommand::PlayHold(uid as u64, sd);
-                self.local_mixer.handle_command(com);
-                outputs.sounds.push(
-                    com,
-                )
+            if let Some(key) = self.layouts[self.layout].note(*k) {
+                let note = self.shift.key_to_midi(key);
+                if self.arp_panel.on {
+                    self.arp.note_on(note);
+                    self.arp_keys.insert(*k, note);
+                    continue;
+                }
+                let mut handles = vec![];
+                for (i, sd) in self.slot_sounds(note, 1.0, None) {
+                    let h = outputs.play(i, sd);
+                    self.held_keys.insert(h, (note, inputs.t, sd));
+                    handles.push((h, i));
+                }
+                self.key_voices.insert(*k, handles);
             }
         }
         let released_keys = inputs.prev_keys.difference(&inputs.curr_keys);
         for k in released_keys {
-            if let Some(note) = kc_to_note(*k) {
-                let uid = (31249577 + self.times_pressed.get(k).unwrap_or(&0)) * khash(12312577 * note as u32);
-                if let Some((_note, t_start, _sd)) = self.held_keys.remove(&uid) {
-                    let t_end = inputs.t as f32;
-                    self.history.push((note, t_start, t_end));
-                    let com = AudioCommand::Release(uid as u64);
-                    self.local_mixer.handle_command(com);
-                    outputs.sounds.push(
-                        com,
-                    )
+            if let Some(note) = self.arp_keys.remove(k) {
+                self.arp.note_off(note);
+            }
+            // one note in the history per key, however many slots it layered onto
+            let mut played = None;
+            for (h, _) in self.key_voices.remove(k).unwrap_or_default() {
+                if let Some((note, t_start, _sd)) = self.held_keys.remove(&h) {
+                    played = Some((note, t_start));
+                    outputs.release(h);
                 }
-                self.times_pressed.insert(*k, *self.times_pressed.get(k).unwrap_or(&0) + 1);
+            }
+            if let Some((note, start)) = played {
+                self.history.push(PlayedNote { note, start, end: inputs.t, vel: 1.0, src: NoteSource::Keys });
+            }
+        }
+
+        let bpm = self.transport.bpm;
+        self.transport_panel.apply(&mut self.transport, inputs.clock);
+        if self.transport.bpm != bpm {
+            self.send(outputs, AudioCommand::SetTempo(self.transport.bpm));
+        }
+
+        // sequencer notes reach the arp once the audio clock gets to them
+        let now = inputs.clock;
+        let (due, later): (Vec<_>, Vec<_>) = self.arp_pending.drain(..).partition(|(t, _, _)| *t <= now);
+        self.arp_pending = later;
+        for (_, note, down) in due {
+            if down { self.arp.note_on(note) } else { self.arp.note_off(note) }
+        }
 
+        // the arp plays a little ahead, every note comes with its own release
+        self.arp_panel.apply(&mut self.arp);
+        self.arp.bpm = self.transport.bpm;
+        let until = inputs.clock + (ARP_LOOKAHEAD * SAMPLE_RATE) as u64;
+        for an in self.arp.run(inputs.clock, until) {
+            let to_t = |s: u64| inputs.t + (s as f32 - inputs.clock as f32) / SAMPLE_RATE;
+            self.history.push(PlayedNote { note: an.note, start: to_t(an.on), end: to_t(an.off), vel: 1.0, src: NoteSource::Arp });
+            for (i, sd) in self.slot_sounds(an.note, 1.0, None) {
+                let h = VoiceHandle::next();
+                self.send(outputs, AudioCommand::At(an.on, GROUP_NONE, Timed::PlayHold(h.0, i, Box::new(sd))));
+                self.send(outputs, AudioCommand::At(an.off, GROUP_NONE, Timed::Release(h.0)));
             }
         }
 
+        // everything on the beat comes off the transport, a tick per sequencer step.
+        // the seq does 16ths whatever the beat is
+        let rate = (16 / self.transport.beat_unit).max(1);
+        self.seq.rate = rate;
+        let last = self.last_tick;
+        let ticks: Vec<Tick> = self.transport.ticks(inputs.clock, until, rate).into_iter()
+            .filter(|t| last.is_none_or(|l| t.raw > l))
+            .collect();
+        if let Some(t) = ticks.last() {
+            self.last_tick = Some(t.raw);
+        }
+        if self.transport_panel.metronome {
+            let bar = self.transport.beats_per_bar.max(1) as u64;
+            for t in ticks.iter().filter(|t| t.pos.is_multiple_of(rate as u64)) {
+                let accent = (t.pos / rate as u64).is_multiple_of(bar);
+                self.send(outputs, AudioCommand::At(t.sample, GROUP_CLICK, Timed::Click(accent)));
+            }
+        }
+        let step_len = self.transport.samples_per_beat() / rate as f64;
+        for sn in self.seq.run(&ticks, step_len) {
+            self.play_step(inputs, outputs, sn, step_len);
+        }
+
+        // while a step is picked for locking the knobs show its locks, and whatever
+        // gets turned this frame becomes one. the slots own values go back after
+        let lock_base = match self.seq_lock {
+            Some(i) => {
+                let step = &self.seq.patterns[self.seq.edit].steps[i];
+                let knobs = &mut self.slots[self.selected].knobs;
+                let base = knobs.build_patch();
+                if step.lock_slot == self.selected {
+                    knobs.apply_patch(&step.locks);
+                }
+                Some((i, self.selected, base))
+            },
+            None => None,
+        };
+
         outputs.canvas.put_rect(inputs.screen_rect, 1.0, Vec4::grey(0.2));
         let r = inputs.screen_rect.dilate_pc(-0.003);
 
         {
-            let w_envelope = 0.3333;
+            let w_envelope = 0.25;
 
             // top
-            let r = r.grid_child(0, 0, 1, 3);
+            let r = r.child(0.0, 0.06, 1.0, 0.26);
+            let knobs = &mut self.slots[self.selected].knobs;
 
             // envelope section
             let r = r.child(0.0, 0.0, w_envelope, 1.0);
@@ -227,10 +1718,10 @@ impl SynthGUI {
                 {
                     let r = r.dilate_pc(-0.01);
                     let r = r.grid_child(0, 0, 5, 1);
-                    self.knobs.a.frame(inputs, outputs, r.grid_child(0, 0, 1, 4));
-                    self.knobs.d.frame(inputs, outputs, r.grid_child(0, 1, 1, 4));
-                    self.knobs.s.frame(inputs, outputs, r.grid_child(0, 2, 1, 4));
-                    self.knobs.r.frame(inputs, outputs, r.grid_child(0, 3, 1, 4));
+                    knobs.a.frame(inputs, outputs, r.grid_child(0, 0, 1, 4));
+                    knobs.d.frame(inputs, outputs, r.grid_child(0, 1, 1, 4));
+                    knobs.s.frame(inputs, outputs, r.grid_child(0, 2, 1, 4));
+                    knobs.r.frame(inputs, outputs, r.grid_child(0, 3, 1, 4));
                 }
             }
             {
@@ -241,11 +1732,11 @@ impl SynthGUI {
                 let r = r.dilate_pc(-0.03);
                 outputs.canvas.put_rect(r, 1.02, v4(0., 0., 0., 1.));
                 
-                let a = self.knobs.a.curr();
-                let d = self.knobs.d.curr();
-                let s = 1.0 - self.knobs.s.curr();
+                let a = knobs.a.curr();
+                let d = knobs.d.curr();
+                let s = 1.0 - knobs.s.curr();
                 let sustime = 0.7;
-                let rel = self.knobs.r.curr();
+                let rel = knobs.r.curr();
 
                 let tot = a+d+sustime+rel;
 
@@ -279,20 +1770,75 @@ impl SynthGUI {
             {
                 let r = r.dilate_pc(-0.01);
                 outputs.canvas.put_rect(r, 1.01, Vec4::new(0.9, 0.2, 0.2, 1.0));
-                outputs.glyphs.push_center_str("oscillator", r.x + r.w/2.0, r.y + 0.1*r.h/2.0, 0.1*r.h/2.5, 0.1*r.h/2.5, 1.2, v4(1.0, 1.0, 1.0, 1.0));
+                let kind = VoiceKind::from_param(knobs.voice.curr());
+                outputs.glyphs.push_center_str(kind.name(), r.x + r.w/2.0, r.y + 0.1*r.h/2.0, 0.1*r.h/2.5, 0.1*r.h/2.5, 1.2, v4(1.0, 1.0, 1.0, 1.0));
                 let r = r.child(0.0, 0.1, 1.0, 0.9);
                 {
                     let r = r.dilate_pc(-0.01);
-                    self.knobs.n.frame(inputs, outputs, r.grid_child(0, 0, 2, 4));
-                    self.knobs.troll.frame(inputs, outputs, r.grid_child(0, 1, 2, 4));
-                    self.knobs.detune.frame(inputs, outputs, r.grid_child(0, 2, 2, 4));
-                    self.knobs.voices.frame(inputs, outputs, r.grid_child(0, 3, 2, 4));
-                    self.knobs.base_freq.frame(inputs, outputs, r.grid_child(1, 1, 2, 4));
-                    if self.knobs.aout.frame(inputs, outputs, r.grid_child(1, 0, 2, 4)) {
-                        let com = AudioCommand::SetVol(db_to_vol(self.knobs.aout.curr()));
-                        outputs.sounds.push(com);
-                        self.local_mixer.handle_command(com);
-                    };
+                    knobs.n.frame(inputs, outputs, r.grid_child(0, 0, 4, 4));
+                    knobs.troll.frame(inputs, outputs, r.grid_child(0, 1, 4, 4));
+                    knobs.detune.frame(inputs, outputs, r.grid_child(0, 2, 4, 4));
+                    knobs.voices.frame(inputs, outputs, r.grid_child(0, 3, 4, 4));
+                    knobs.voice.frame(inputs, outputs, r.grid_child(1, 0, 4, 4));
+                    knobs.coarse.frame(inputs, outputs, r.grid_child(1, 1, 4, 4));
+
+                    // both sample based voices pick from the same bank
+                    if kind == VoiceKind::Sampler || kind == VoiceKind::Granular {
+                        knobs.smp_id.frame(inputs, outputs, r.grid_child(1, 2, 4, 4));
+                        let rr = r.grid_child(1, 3, 4, 4);
+                        knobs.smp_root.frame(inputs, outputs, rr);
+                        note_label(outputs, rr, knobs.smp_root.curr().round() as i32);
+                        let id = knobs.smp_id.curr().round() as usize;
+                        let name = self.sample_names[id].as_deref().unwrap_or("drop a wav");
+                        let rn = r.child(0.0, 1.0, 1.0, 0.0);
+                        let w = 0.1 * r.h / 2.5;
+                        outputs.glyphs.push_center_str(name, rn.x + rn.w/2.0, rn.y - w, w, w, 1.2, v4(1.0, 1.0, 1.0, 1.0));
+                    }
+
+                    // the last two columns are whatever the voice kind needs
+                    match kind {
+                        VoiceKind::Additive => {
+                            knobs.osc2_wave.frame(inputs, outputs, r.grid_child(2, 0, 4, 4));
+                            knobs.osc2_semi.frame(inputs, outputs, r.grid_child(2, 1, 4, 4));
+                            knobs.osc2_fine.frame(inputs, outputs, r.grid_child(2, 2, 4, 4));
+                            knobs.osc2_mix.frame(inputs, outputs, r.grid_child(2, 3, 4, 4));
+                            knobs.osc2_sync.frame(inputs, outputs, r.grid_child(3, 0, 4, 4));
+                            knobs.osc2_ring.frame(inputs, outputs, r.grid_child(3, 1, 4, 4));
+                            knobs.sub_level.frame(inputs, outputs, r.grid_child(3, 2, 4, 4));
+                            knobs.sub_oct.frame(inputs, outputs, r.grid_child(3, 3, 4, 4));
+                        },
+                        VoiceKind::String => {
+                            knobs.str_damp.frame(inputs, outputs, r.grid_child(2, 0, 4, 4));
+                            knobs.str_pick.frame(inputs, outputs, r.grid_child(2, 1, 4, 4));
+                            knobs.str_excite.frame(inputs, outputs, r.grid_child(2, 2, 4, 4));
+                        },
+                        // keyframes go on the timeline under the rack
+                        VoiceKind::Morph => {},
+                        VoiceKind::Sampler => {
+                            knobs.smp_loop.frame(inputs, outputs, r.grid_child(2, 0, 4, 4));
+                            knobs.smp_xfade.frame(inputs, outputs, r.grid_child(2, 1, 4, 4));
+                            knobs.smp_start.frame(inputs, outputs, r.grid_child(3, 0, 4, 4));
+                            knobs.smp_end.frame(inputs, outputs, r.grid_child(3, 1, 4, 4));
+                        },
+                        // the generator goes where the morph timeline does
+                        VoiceKind::Sfx => {},
+                        VoiceKind::Drum => {
+                            knobs.drum_kind.frame(inputs, outputs, r.grid_child(2, 0, 4, 4));
+                            knobs.drum_decay.frame(inputs, outputs, r.grid_child(2, 1, 4, 4));
+                            knobs.drum_tone.frame(inputs, outputs, r.grid_child(2, 2, 4, 4));
+                            knobs.drum_punch.frame(inputs, outputs, r.grid_child(2, 3, 4, 4));
+                        },
+                        VoiceKind::Granular => {
+                            knobs.gr_size.frame(inputs, outputs, r.grid_child(2, 0, 4, 4));
+                            knobs.gr_density.frame(inputs, outputs, r.grid_child(2, 1, 4, 4));
+                            knobs.gr_pos.frame(inputs, outputs, r.grid_child(2, 2, 4, 4));
+                            knobs.gr_scan.frame(inputs, outputs, r.grid_child(2, 3, 4, 4));
+                            knobs.gr_pos_jit.frame(inputs, outputs, r.grid_child(3, 0, 4, 4));
+                            knobs.gr_pitch_jit.frame(inputs, outputs, r.grid_child(3, 1, 4, 4));
+                            knobs.gr_spray.frame(inputs, outputs, r.grid_child(3, 2, 4, 4));
+                            knobs.gr_window.frame(inputs, outputs, r.grid_child(3, 3, 4, 4));
+                        },
+                    }
                 }
             }
 
@@ -316,21 +1862,21 @@ impl SynthGUI {
                 let r = r.child(0.0, 0.1, 1.0, 0.9);
                 {
                     let r = r.dilate_pc(-0.01);
-                    self.knobs.amp.frame(inputs, outputs, r.grid_child(0, 0, 3, 4));
-                    self.knobs.cut.frame(inputs, outputs, r.grid_child(1, 0, 3, 4));
-                    self.knobs.cur.frame(inputs, outputs, r.grid_child(1, 1, 3, 4));
-                    self.knobs.cdt.frame(inputs, outputs, r.grid_child(2, 0, 3, 4));
-                    self.knobs.cdr.frame(inputs, outputs, r.grid_child(2, 1, 3, 4));
-                    self.knobs.hard_clip.frame(inputs, outputs, r.grid_child(1, 2, 3, 4));
+                    knobs.amp.frame(inputs, outputs, r.grid_child(0, 0, 3, 4));
+                    knobs.cut.frame(inputs, outputs, r.grid_child(1, 0, 3, 4));
+                    knobs.cur.frame(inputs, outputs, r.grid_child(1, 1, 3, 4));
+                    knobs.cdt.frame(inputs, outputs, r.grid_child(2, 0, 3, 4));
+                    knobs.cdr.frame(inputs, outputs, r.grid_child(2, 1, 3, 4));
+                    knobs.hard_clip.frame(inputs, outputs, r.grid_child(1, 2, 3, 4));
 
                     let r = r.grid_child(0, 1, 3, 4).child(0.0, 0.0, 1.0, 3.0);
                     outputs.canvas.put_rect(r, 1.02, v4(0., 0., 0., 1.));
                     let rh = (100.0 + max_db).max(0.0) / 100.0;
-                    let hc_db = self.knobs.hard_clip.curr();
+                    let hc_db = knobs.hard_clip.curr();
                     let hc_line = (100.0 + hc_db).max(0.0) / 100.0;
-                    let cu_db = self.knobs.cut.curr();
+                    let cu_db = knobs.cut.curr();
                     let cu_line = (100.0 + cu_db).max(0.0) / 100.0;
-                    let cd_db = self.knobs.cdt.curr();
+                    let cd_db = knobs.cdt.curr();
                     let cd_line = (100.0 + cd_db).max(0.0) / 100.0;
 
                     let hc_vol = db_to_vol(hc_db);
@@ -347,31 +1893,63 @@ impl SynthGUI {
                     outputs.canvas.put_rect(r.child(0.0, 1.0 - cd_line, 1.0, 0.01), 1.03, v4(0., 1., 0., 1.));
                 }
             }
+
+            // Shaper
+            let r = r.child(1.0, 0.0, 1.0, 1.0);
+            {
+                let r = r.dilate_pc(-0.01);
+                outputs.canvas.put_rect(r, 1.01, Vec4::new(0.9, 0.2, 0.2, 1.0));
+                outputs.glyphs.push_center_str("shaper", r.x + r.w/2.0, r.y + 0.1*r.h/2.0, 0.1*r.h/2.5, 0.1*r.h/2.5, 1.2, v4(1.0, 1.0, 1.0, 1.0));
+                let r = r.child(0.0, 0.1, 1.0, 0.9);
+                {
+                    let r = r.dilate_pc(-0.01);
+                    knobs.dist_curve.frame(inputs, outputs, r.grid_child(0, 0, 3, 4));
+                    knobs.dist_drive.frame(inputs, outputs, r.grid_child(0, 1, 3, 4));
+                    knobs.dist_bias.frame(inputs, outputs, r.grid_child(0, 2, 3, 4));
+                    knobs.dist_os.frame(inputs, outputs, r.grid_child(0, 3, 3, 4));
+                    knobs.crush_bits.frame(inputs, outputs, r.grid_child(1, 0, 3, 4));
+                    knobs.crush_rate.frame(inputs, outputs, r.grid_child(1, 1, 3, 4));
+                    knobs.flt_cut.frame(inputs, outputs, r.grid_child(1, 2, 3, 4));
+                    knobs.flt_res.frame(inputs, outputs, r.grid_child(1, 3, 3, 4));
+                    knobs.fmt_vowel.frame(inputs, outputs, r.grid_child(2, 0, 3, 4));
+                    knobs.fmt_rate.frame(inputs, outputs, r.grid_child(2, 1, 3, 4));
+                    knobs.fmt_depth.frame(inputs, outputs, r.grid_child(2, 2, 3, 4));
+                    knobs.fmt_mix.frame(inputs, outputs, r.grid_child(2, 3, 3, 4));
+                }
+            }
         }
+        self.slots_frame(inputs, outputs, r.child(0.0, 0.0, 1.0, 0.06));
+        self.rack_frame(inputs, outputs, r.child(0.0, 0.32, 0.75, 0.2));
+        self.delay_frame(inputs, outputs, r.child(0.75, 0.32, 0.25, 0.2));
+
         // FFT
         // how many times to pump the mixer, 44100/60 lol?
         // ive got t, is it accurate enough
         // definitely pump it better please
         while self.local_mixer.sample_count < (inputs.t * 44100.) as u64 {
-            self.sample_ringbuf[self.rb_head] = self.local_mixer.tick() / self.local_mixer.out_vol;
+            let (l, r) = self.local_mixer.tick();
+            self.sample_ringbuf[self.rb_head] = 0.5 * (l + r) / self.local_mixer.out_vol;
             self.rb_head = (self.rb_head + 1) % FFT_SIZE;
         }
         
         let mut planner = FftPlanner::new();
         let mut buf = [Complex{re: 0.0, im: 0.0}; FFT_SIZE];
-        for i in 0..FFT_SIZE {
+        for (i, b) in buf.iter_mut().enumerate() {
             let x = self.sample_ringbuf[(self.rb_head + i) % FFT_SIZE] * blackman(i, FFT_SIZE);
-            buf[i] = Complex{re: x, im: 0.0};
+            *b = Complex{re: x, im: 0.0};
         }
         let fft = planner.plan_fft_forward(FFT_SIZE);
         fft.process(&mut buf);
 
-        // now display it
+        // now display it, log frequency so the eq lines up with what you hear
         let fft_display_w = FFT_SIZE/8;
-        let fft_height = 64;
+        let fft_height = 128;
+        let bin_hz = 44100.0 / FFT_SIZE as f32;
         let mut tb = TextureBuffer::new(fft_display_w, fft_height);
         for i in 0..fft_display_w {
-            let h = -vol_to_db(buf[i].re / FFT_SIZE as f32) / 100.0;
+            let f = x_to_freq(i as f32 / fft_display_w as f32);
+            let bin = ((f / bin_hz) as usize).min(FFT_SIZE/2 - 1);
+            let h = -vol_to_db(buf[bin].norm() / FFT_SIZE as f32) / 100.0;
             // let h = -2.0-vol_to_db(buf[i].abs()/buf.len() as f32) / 100.0;
 
             for j in 0..fft_height {
@@ -382,53 +1960,172 @@ impl SynthGUI {
                 }
             }
         }
-        
 
+        // mid, the morph timeline takes half when its in use
+        let r_mid = r.child(0.0, 0.52, 1.0, 0.18);
+        let kind = VoiceKind::from_param(self.slots[self.selected].knobs.voice.curr());
+        let side = kind == VoiceKind::Morph || kind == VoiceKind::Sfx;
+        let r_fft = if side { r_mid.child(0.0, 0.0, 0.5, 1.0) } else { r_mid }.dilate_pc(-0.01);
+        self.eq_frame(inputs, outputs, r_fft, &mut tb);
+        outputs.set_texture.push((tb, 0));
+        outputs.draw_texture.push((r_fft, 0));
+        match kind {
+            VoiceKind::Morph => self.morph_frame(inputs, outputs, r_mid.child(0.5, 0.0, 0.5, 1.0)),
+            VoiceKind::Sfx => self.sfx_frame(inputs, outputs, r_mid.child(0.5, 0.0, 0.5, 1.0)),
+            _ => {},
+        }
 
-        {
-            // mid
-            let r = r.grid_child(0, 1, 1, 3);
-            let r = r.dilate_pc(-0.01);
-            outputs.set_texture.push((tb, 0));
-            outputs.draw_texture.push((r, 0));
-            // outputs.canvas.put_rect(r, 1.01, Vec4::new(0.0, 0.8, 0.4, 1.0));
+        // picking another slot mid frame mustnt get the first ones values
+        if let Some((i, sel, base)) = lock_base {
+            let knobs = &mut self.slots[sel].knobs;
+            let step = &mut self.seq.patterns[self.seq.edit].steps[i];
+            for (name, v) in knobs.build_patch().values {
+                if base.get(&name) != Some(v) {
+                    // locking on another slot starts over, a step only locks one
+                    if step.lock_slot != sel {
+                        step.locks = Patch::default();
+                        step.lock_slot = sel;
+                    }
+                    step.locks.set(&name, v);
+                }
+            }
+            knobs.apply_patch(&base);
         }
 
-        {
-            // bot
-            let r = r.grid_child(0, 2, 1, 3);
-            let r = r.dilate_pc(-0.01);
-            outputs.canvas.put_rect(r, 1.01, v4(0., 0., 0., 1.));
-
-            // draw history notes
-            for &(note, start, end) in self.history.iter() {
-                if end > inputs.t as f32 - 10.0 {
-                    let r = r.grid_child(0, (NUM_NOTES - 1 - note) as i32, 1, NUM_NOTES as i32);
-                    let r = r.child(1.0 - (inputs.t as f32 - start) / 10.0, 0.0, (end - start) / 10.0, 1.0);
-
-                    let h = 72.0 * (note / 7) as f32;
-                    let s = 1.0 - (note % 7) as f32 / 8.0;
-                    let v = 1.0;
-
-                    let c = Vec4::new(h, s, v, 1.0).hsv_to_rgb();
-                    outputs.canvas.put_rect(r, 1.2, c);
+        self.pads_frame(inputs, outputs, r.child(0.0, 0.7, 0.46, 0.06));
+        self.transport_frame(inputs, outputs, r.child(0.46, 0.7, 0.24, 0.06));
+        self.arp_frame(inputs, outputs, r.child(0.7, 0.7, 0.3, 0.06));
+
+        // bottom, the note history or the sequencer grid with its controls down the left
+        let r = r.child(0.0, 0.76, 1.0, 0.24).dilate_pc(-0.01);
+        outputs.canvas.put_rect(r, 1.01, v4(0., 0., 0., 1.));
+        let (r_ctl, r_view) = r.split_lr(0.08);
+        if button(inputs, outputs, r_ctl.grid_child(0, 0, 1, 8).dilate_pc(-0.05), if self.seq_view { "notes" } else { "seq" }, false) {
+            self.seq_view = !self.seq_view;
+            self.seq_lock = None;
+        }
+        if self.seq_view {
+            self.seq_frame(inputs, outputs, r_ctl.child(0.0, 0.125, 1.0, 0.875), r_view);
+        } else {
+            for format in 0..2 {
+                if button(inputs, outputs, r_ctl.grid_child(0, 1 + format, 1, 8).dilate_pc(-0.05), &format!("midi {}", format), false) {
+                    self.export_history(inputs, format as u16);
+                }
+            }
+            if button(inputs, outputs, r_ctl.grid_child(0, 3, 1, 8).dilate_pc(-0.05), "clear", false) {
+                self.history.clear();
+            }
+            self.history_frame(inputs, outputs, r_view);
+        }
+
+        // everything sent this frame goes to the local mixer as well so the FFT sees it.
+        // At times are on the audio clock, the local mixer runs off t, so shift them over
+        for com in outputs.sounds[first_sound..].iter() {
+            let com = match com.clone() {
+                AudioCommand::At(t, group, timed) => AudioCommand::At((t + self.local_mixer.sample_count).saturating_sub(inputs.clock), group, timed),
+                com => com,
+            };
+            self.local_mixer.handle_command(com);
+        }
+    }
+
+    // held keys follow octave and transpose. a slot that cant play the new note (out of
+    // range, or the tuning leaves it out) cuts its voice rather than leave it at the old pitch
+    fn retune_held(&mut self, outputs: &mut FrameOutputs) {
+        let layout = &self.layouts[self.layout];
+        let keys: Vec<(i32, Vec<(VoiceHandle, usize)>)> = self.key_voices.iter()
+            .filter_map(|(k, hs)| layout.note(*k).map(|key| (self.shift.key_to_midi(key), hs.clone())))
+            .collect();
+        for (note, handles) in keys {
+            let sounds = self.slot_sounds(note, 1.0, None);
+            for (h, i) in handles {
+                match sounds.iter().find(|(j, _)| *j == i) {
+                    Some((_, sd)) => {
+                        outputs.retune(h, sd.f);
+                        if let Some(held) = self.held_keys.get_mut(&h) {
+                            held.0 = note;
+                        }
+                    },
+                    None => {
+                        outputs.stop(h);
+                        self.held_keys.remove(&h);
+                    },
                 }
             }
+        }
+    }
+
+    // notes still sounding or scheduled ahead dont count yet
+    fn export_history(&self, inputs: &FrameInputState, format: u16) {
+        let notes: Vec<PlayedNote> = self.history.iter().filter(|n| n.end <= inputs.t).copied().collect();
+        if notes.is_empty() {
+            println!("nothing to export");
+            return;
+        }
+        let tempo = SmfTempo {
+            bpm: self.transport.bpm,
+            beats_per_bar: self.transport.beats_per_bar,
+            beat_unit: self.transport.beat_unit,
+        };
+        // bar 1 of the transport, on the same clock as the history
+        let origin = if self.transport.started {
+            Some(inputs.t + ((self.transport.zero_sample() - inputs.clock as f64) / SAMPLE_RATE as f64) as f32)
+        } else {
+            None
+        };
+        let path = Path::new(EXPORT_DIR).join(format!("take_{}", inputs.seed)).with_extension("mid");
+        match save_smf(&path, &notes, &tempo, origin, format) {
+            Ok(()) => println!("saved {}", path.display()),
+            Err(e) => println!("couldnt save: {}", e),
+        }
+    }
 
-            // draw held notes
-            for &(note, start, _sd) in self.held_keys.values() {
-                let r = r.grid_child(0, (NUM_NOTES - 1 - note) as i32, 1, NUM_NOTES as i32);
-                let end = inputs.t;
-                let r = r.child(1.0 - (inputs.t as f32 - start) / 10.0, 0.0, (end-start) / 10.0, 1.0);
+    fn history_frame(&mut self, inputs: &FrameInputState, outputs: &mut FrameOutputs, r: Rect) {
+        // rows cover whatever the keyboard reaches plus anything still on screen
+        let visible = self.history.iter().filter(|n| n.end > inputs.t - 10.0).map(|n| n.note)
+            .chain(self.held_keys.values().map(|(note, _, _)| *note));
+        let (lo, hi) = visible.fold((self.shift.key_to_midi(0), self.shift.key_to_midi(self.layouts[self.layout].highest())), |(lo, hi), n| (lo.min(n), hi.max(n)));
+        let rows = hi - lo + 1;
+        let note_colour = |note: i32| {
+            let h = 72.0 * octave(note) as f32;
+            let s = 1.0 - pitch_class(note) as f32 / 16.0;
+            Vec4::new(h, s, 1.0, 1.0).hsv_to_rgb()
+        };
 
-                let h = 72.0 * (note / 7) as f32;
-                let s = 1.0 - (note % 7) as f32 / 8.0;
-                let v = 1.0;
+        // C on every octave down the left
+        let (r_names, r) = r.split_lr(0.03);
+        for note in lo..=hi {
+            if pitch_class(note) == 0 || note == lo {
+                let rn = r_names.grid_child(0, hi - note, 1, rows);
+                let w = rn.h.min(rn.w / 3.0);
+                outputs.glyphs.push_center_str(&note_name(note), rn.x + rn.w/2.0, rn.y + rn.h/2.0, w, w, 1.2, v4(1.0, 1.0, 1.0, 1.0));
+            }
+        }
 
-                let c = Vec4::new(h, s, v, 1.0).hsv_to_rgb();
-                outputs.canvas.put_rect(r, 1.2, c);
+        // draw history notes
+        for n in self.history.iter() {
+            if n.end > inputs.t - 10.0 {
+                let r = r.grid_child(0, hi - n.note, 1, rows);
+                let r = r.child(1.0 - (inputs.t - n.start) / 10.0, 0.0, (n.end - n.start) / 10.0, 1.0);
+                outputs.canvas.put_rect(r, 1.2, note_colour(n.note));
             }
         }
+
+        // draw held notes, named
+        let mut held: Vec<i32> = self.held_keys.values().map(|(note, _, _)| *note).collect();
+        held.sort();
+        held.dedup();
+        for &(note, start, _sd) in self.held_keys.values() {
+            let r = r.grid_child(0, hi - note, 1, rows);
+            let end = inputs.t;
+            let r = r.child(1.0 - (inputs.t - start) / 10.0, 0.0, (end-start) / 10.0, 1.0);
+            outputs.canvas.put_rect(r, 1.2, note_colour(note));
+        }
+        let names: Vec<String> = held.iter().map(|n| note_name(*n)).collect();
+        let w = 0.06 * r.h;
+        outputs.glyphs.push_str(&names.join(" "), r.x + w, r.y + w, w, w, 1.3, v4(1.0, 1.0, 1.0, 1.0));
+        let layout = &self.layouts[self.layout].name;
+        outputs.glyphs.push_str(layout, r.right() - w * (layout.len() as f32 + 1.0), r.y + w, w, w, 1.3, v4(1.0, 1.0, 1.0, 1.0));
     }
 }
 
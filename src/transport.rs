@@ -0,0 +1,135 @@
+use crate::dsp::SAMPLE_RATE;
+
+// Song position, worked out from the audio clock rather than the frame timer so
+// anything scheduled off it lands on the sample. Beats are whatever beat_unit says,
+// bpm counts those. The loop region is in whole bars.
+
+pub const BEAT_UNITS: [u32; 4] = [2, 4, 8, 16];
+
+#[derive(Debug, Clone, Copy)]
+pub struct Tick {
+    pub raw: u64,       // counts up forever while playing, loops dont reset it
+    pub pos: u64,       // where in the song, in ticks, after looping
+    pub sample: u64,
+}
+
+pub struct Transport {
+    pub bpm: f32,
+    pub beats_per_bar: u32,
+    pub beat_unit: u32,
+    pub playing: bool,
+    pub looping: bool,
+    pub loop_start: u32,    // bars
+    pub loop_len: u32,      // bars
+    pub started: bool,      // played at least once, so theres a bar 1 somewhere
+
+    // beat position is origin + (sample - start) / samples_per_beat, re-anchored on tempo changes
+    start: u64,
+    origin: f64,
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Transport {
+            bpm: 120.0,
+            beats_per_bar: 4,
+            beat_unit: 4,
+            playing: false,
+            looping: false,
+            loop_start: 0,
+            loop_len: 4,
+            started: false,
+            start: 0,
+            origin: 0.0,
+        }
+    }
+}
+
+impl Transport {
+    pub fn samples_per_beat(&self) -> f64 {
+        SAMPLE_RATE as f64 * 60.0 / self.bpm.max(1.0) as f64
+    }
+
+    // from the top of the loop if its on
+    pub fn play(&mut self, now: u64) {
+        self.playing = true;
+        self.started = true;
+        self.start = now;
+        self.origin = if self.looping { self.loop_beats().0 } else { 0.0 };
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn set_bpm(&mut self, bpm: f32, now: u64) {
+        if bpm == self.bpm {
+            return;
+        }
+        if self.playing {
+            self.origin = self.raw_beat(now);
+            self.start = now;
+        }
+        self.bpm = bpm;
+    }
+
+    fn loop_beats(&self) -> (f64, f64) {
+        let bar = self.beats_per_bar as f64;
+        (self.loop_start as f64 * bar, (self.loop_start + self.loop_len.max(1)) as f64 * bar)
+    }
+
+    fn raw_beat(&self, sample: u64) -> f64 {
+        self.origin + (sample as f64 - self.start as f64) / self.samples_per_beat()
+    }
+
+    // past the end of the loop goes back round, before it plays through as normal
+    fn wrap(&self, beat: f64) -> f64 {
+        let (ls, le) = self.loop_beats();
+        if self.looping && beat >= le {
+            ls + (beat - ls) % (le - ls)
+        } else {
+            beat
+        }
+    }
+
+    // where beat 0 is (or would have been) at the current tempo, in samples. can be negative
+    pub fn zero_sample(&self) -> f64 {
+        self.start as f64 - self.origin * self.samples_per_beat()
+    }
+
+    pub fn beat(&self, now: u64) -> f64 {
+        if !self.playing {
+            return 0.0;
+        }
+        self.wrap(self.raw_beat(now).max(0.0))
+    }
+
+    // 1 based bar and beat, for showing
+    pub fn bar_beat(&self, now: u64) -> (u32, u32) {
+        let b = self.beat(now);
+        let bar = self.beats_per_bar as f64;
+        ((b / bar) as u32 + 1, (b % bar) as u32 + 1)
+    }
+
+    // every tick in [from, until), per_beat of them to a beat
+    pub fn ticks(&self, from: u64, until: u64, per_beat: u32) -> Vec<Tick> {
+        let mut out = vec![];
+        if !self.playing || until <= from {
+            return out;
+        }
+        let pb = per_beat.max(1) as f64;
+        let spb = self.samples_per_beat();
+        let first = (self.raw_beat(from.max(self.start)) * pb).ceil().max(0.0) as u64;
+        let mut k = first;
+        loop {
+            let beat = k as f64 / pb;
+            let sample = self.start as f64 + (beat - self.origin) * spb;
+            if sample >= until as f64 {
+                break;
+            }
+            out.push(Tick { raw: k, pos: (self.wrap(beat) * pb).round() as u64, sample: sample.max(0.0) as u64 });
+            k += 1;
+        }
+        out
+    }
+}
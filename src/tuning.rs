@@ -0,0 +1,237 @@
+use std::path::Path;
+
+// Scala scales (.scl) and keyboard mappings (.kbm). The file formats are at
+// https://www.huygens-fokker.org/scala/scl_format.html and help_kbm.html
+
+// degrees in cents above the root, the last one is the period (usually the octave).
+// the root itself isnt stored
+#[derive(Debug, Clone)]
+pub struct Scale {
+    pub name: String,
+    pub cents: Vec<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyMap {
+    pub size: usize,            // 0 is a straight line, one key per degree
+    pub first: i32,
+    pub last: i32,
+    pub middle: i32,            // key that gets map[0]
+    pub ref_key: i32,
+    pub ref_freq: f64,
+    pub octave_degree: usize,   // 0 means the scales own period
+    pub map: Vec<Option<usize>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Tuning {
+    pub scale: Scale,
+    pub keymap: Option<KeyMap>,
+}
+
+// scala files are full of ! comments, those dont count as lines
+fn data_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines().map(|l| l.trim_end_matches('\r')).filter(|l| !l.starts_with('!'))
+}
+
+fn parse_pitch(s: &str) -> Result<f64, String> {
+    let s = s.split_whitespace().next().ok_or("empty pitch line")?;
+    let bad = || format!("bad pitch {}", s);
+    if s.contains('.') {
+        return s.parse::<f64>().map_err(|_| bad());
+    }
+    let (num, den) = match s.split_once('/') {
+        Some((n, d)) => (n.parse::<f64>().map_err(|_| bad())?, d.parse::<f64>().map_err(|_| bad())?),
+        None => (s.parse::<f64>().map_err(|_| bad())?, 1.0),
+    };
+    if num <= 0.0 || den <= 0.0 {
+        return Err(bad());
+    }
+    Ok(1200.0 * (num / den).log2())
+}
+
+impl Scale {
+    pub fn equal(n: usize) -> Scale {
+        Scale {
+            name: format!("{}-tet", n),
+            cents: (1..=n).map(|i| 1200.0 * i as f64 / n as f64).collect(),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Scale, String> {
+        let mut lines = data_lines(text);
+        let name = lines.next().ok_or("empty scl")?.trim().to_string();
+        let n: usize = lines.next().and_then(|l| l.split_whitespace().next())
+            .and_then(|l| l.parse().ok())
+            .ok_or("bad note count")?;
+        let cents = lines.take(n).map(parse_pitch).collect::<Result<Vec<_>, _>>()?;
+        if n == 0 || cents.len() != n {
+            return Err(format!("wanted {} pitches, got {}", n, cents.len()));
+        }
+        Ok(Scale { name, cents })
+    }
+
+    pub fn len(&self) -> usize {
+        self.cents.len()
+    }
+
+    pub fn period(&self) -> f64 {
+        self.cents[self.len() - 1]
+    }
+
+    // any degree, wrapping round the period both ways
+    pub fn degree_cents(&self, d: i32) -> f64 {
+        let n = self.len() as i32;
+        let (oct, deg) = (d.div_euclid(n), d.rem_euclid(n));
+        let c = if deg == 0 { 0.0 } else { self.cents[deg as usize - 1] };
+        oct as f64 * self.period() + c
+    }
+}
+
+impl Default for KeyMap {
+    // what scala does with no kbm, middle C on the root and A4 at 440
+    fn default() -> Self {
+        KeyMap {
+            size: 0,
+            first: 0,
+            last: 127,
+            middle: 60,
+            ref_key: 69,
+            ref_freq: 440.0,
+            octave_degree: 0,
+            map: vec![],
+        }
+    }
+}
+
+impl KeyMap {
+    pub fn parse(text: &str) -> Result<KeyMap, String> {
+        let mut lines = data_lines(text).map(|l| l.split_whitespace().next().unwrap_or(""));
+        let mut int = |what: &str| -> Result<i64, String> {
+            lines.next().and_then(|l| l.parse().ok()).ok_or(format!("bad {}", what))
+        };
+        let size = int("map size")?.max(0) as usize;
+        let first = int("first key")? as i32;
+        let last = int("last key")? as i32;
+        let middle = int("middle key")? as i32;
+        let ref_key = int("reference key")? as i32;
+        let ref_freq = lines.next().and_then(|l| l.parse::<f64>().ok()).ok_or("bad reference frequency")?;
+        let octave_degree = lines.next().and_then(|l| l.parse::<usize>().ok()).ok_or("bad octave degree")?;
+        // short maps are allowed, the rest are unmapped
+        let mut map: Vec<Option<usize>> = lines.take(size).map(|l| l.parse().ok()).collect();
+        map.resize(size, None);
+        if ref_freq <= 0.0 {
+            return Err("reference frequency has to be positive".to_string());
+        }
+        Ok(KeyMap { size, first, last, middle, ref_key, ref_freq, octave_degree, map })
+    }
+
+    // cents above the root for a key, None if the key isnt mapped
+    fn key_cents(&self, scale: &Scale, key: i32) -> Option<f64> {
+        if key < self.first || key > self.last {
+            return None;
+        }
+        let d = key - self.middle;
+        if self.size == 0 {
+            return Some(scale.degree_cents(d));
+        }
+        let (rep, idx) = (d.div_euclid(self.size as i32), d.rem_euclid(self.size as i32));
+        let deg = self.map[idx as usize]?;
+        let octave = match self.octave_degree {
+            0 => scale.period(),
+            o => scale.degree_cents(o as i32),
+        };
+        Some(rep as f64 * octave + scale.degree_cents(deg as i32))
+    }
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Tuning { scale: Scale::equal(12), keymap: None }
+    }
+}
+
+impl Tuning {
+    pub fn name(&self) -> &str {
+        &self.scale.name
+    }
+
+    // with no kbm its scalas default mapping, but hung off our A4
+    fn keymap(&self, a4: f32) -> KeyMap {
+        self.keymap.clone().unwrap_or(KeyMap { ref_freq: a4 as f64, ..KeyMap::default() })
+    }
+
+    // frequency of a MIDI key, None if the mapping leaves it out
+    pub fn freq(&self, key: i32, a4: f32) -> Option<f32> {
+        let km = self.keymap(a4);
+        // the reference key might not be mapped itself, scala still pitches from it
+        let ref_cents = km.key_cents(&self.scale, km.ref_key).unwrap_or_else(|| self.scale.degree_cents(km.ref_key - km.middle));
+        let c = km.key_cents(&self.scale, key)?;
+        Some((km.ref_freq * 2.0f64.powf((c - ref_cents) / 1200.0)) as f32)
+    }
+}
+
+pub fn load_scl(path: &Path) -> Result<Scale, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Scale::parse(&text)
+}
+
+pub fn load_kbm(path: &Path) -> Result<KeyMap, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    KeyMap::parse(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn scl_ratios_and_cents() {
+        let text = "! test.scl\n!\nsome just thing\n 4\n!\n 9/8\n 386.31371 cents\n3/2\n2\n";
+        let s = Scale::parse(text).unwrap();
+        assert_eq!(s.name, "some just thing");
+        assert_eq!(s.len(), 4);
+        assert!(close(s.cents[0], 1200.0 * (9.0f64 / 8.0).log2()));
+        assert!(close(s.cents[1], 386.31371));
+        assert!(close(s.cents[2], 1200.0 * 1.5f64.log2()));
+        assert!(close(s.period(), 1200.0));
+        assert!(close(s.degree_cents(-1), s.cents[2] - 1200.0));
+        assert!(Scale::parse("short\n3\n9/8\n3/2\n").is_err());
+        assert!(Scale::parse("bad\n1\n-3/2\n").is_err());
+    }
+
+    #[test]
+    fn kbm_with_unmapped_keys() {
+        let text = "! skips the black keys\n12\n0\n127\n60\n69\n440.0\n7\n\
+                    0\nx\n1\nx\n2\n3\nx\n4\nx\n5\nx\n6\n";
+        let km = KeyMap::parse(text).unwrap();
+        assert_eq!(km.size, 12);
+        assert_eq!(km.octave_degree, 7);
+        assert_eq!(km.map[1], None);
+        assert_eq!(km.map[11], Some(6));
+
+        let t = Tuning { scale: Scale::equal(7), keymap: Some(km) };
+        assert_eq!(t.freq(61, 440.0), None);
+        assert!((t.freq(69, 440.0).unwrap() - 440.0).abs() < 1e-3);
+        // an octave of white keys is a period of the 7 note scale
+        assert!((t.freq(81, 440.0).unwrap() - 880.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn twelve_tet_at_440() {
+        let t = Tuning::default();
+        assert!((t.freq(69, 440.0).unwrap() - 440.0).abs() < 1e-3);
+        assert!((t.freq(81, 440.0).unwrap() - 880.0).abs() < 1e-3);
+        assert!((t.freq(57, 440.0).unwrap() - 220.0).abs() < 1e-3);
+        assert!((t.freq(60, 440.0).unwrap() - 261.6256).abs() < 1e-3);
+        for key in 0..128 {
+            let f = t.freq(key, 440.0).unwrap() as f64;
+            let back = 69.0 + 12.0 * (f / 440.0).log2();
+            assert!((back - key as f64).abs() < 1e-3);
+        }
+    }
+}
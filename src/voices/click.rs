@@ -0,0 +1,30 @@
+use crate::dsp::*;
+use crate::kmath::*;
+
+// Metronome click. A short sine blip with a fast decay, higher on the first beat of
+// the bar. It skips the slots entirely so effects and meters never see it.
+
+const CLICK_HZ: f32 = 1000.0;
+const ACCENT_HZ: f32 = 1600.0;
+const CLICK_DECAY: f32 = 0.03;
+
+pub struct ClickVoice {
+    age: u64,
+    f: f32,
+}
+
+impl ClickVoice {
+    pub fn new(accent: bool) -> ClickVoice {
+        ClickVoice { age: 0, f: if accent { ACCENT_HZ } else { CLICK_HZ } }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.age as f32 > 8.0 * CLICK_DECAY * SAMPLE_RATE
+    }
+
+    pub fn tick(&mut self) -> f32 {
+        let t = self.age as f32 / SAMPLE_RATE;
+        self.age += 1;
+        (2.0 * PI * self.f * t).sin() * (-t / CLICK_DECAY).exp()
+    }
+}
@@ -0,0 +1,154 @@
+use crate::dsp::*;
+use crate::kmath::*;
+
+// Percussion. Each one is a pitch swept oscillator and/or filtered noise with short exponential
+// envelopes of its own, so the ADSR is skipped and the voice ends itself when its done.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DrumKind {
+    Kick,
+    Snare,
+    Hat,
+    Tom,
+    Clap,
+}
+
+impl DrumKind {
+    pub const ALL: [DrumKind; 5] = [DrumKind::Kick, DrumKind::Snare, DrumKind::Hat, DrumKind::Tom, DrumKind::Clap];
+
+    pub fn from_param(x: f32) -> DrumKind {
+        let i = (x.round().max(0.0) as usize).min(DrumKind::ALL.len() - 1);
+        DrumKind::ALL[i]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DrumKind::Kick => "kick",
+            DrumKind::Snare => "snare",
+            DrumKind::Hat => "hat",
+            DrumKind::Tom => "tom",
+            DrumKind::Clap => "clap",
+        }
+    }
+
+    // tune, decay, tone, punch that sound like the thing
+    pub fn defaults(&self) -> (f32, f32, f32, f32) {
+        match self {
+            DrumKind::Kick => (50.0, 0.4, 0.3, 0.6),
+            DrumKind::Snare => (180.0, 0.2, 0.6, 0.3),
+            DrumKind::Hat => (400.0, 0.06, 0.5, 0.0),
+            DrumKind::Tom => (110.0, 0.3, 0.2, 0.4),
+            DrumKind::Clap => (1200.0, 0.25, 0.5, 0.0),
+        }
+    }
+}
+
+// tone is 0..1 and means brightness or noise amount depending on the drum,
+// punch is how far the pitch drops from at the start
+#[derive(Clone, Copy)]
+pub struct DrumParams {
+    pub kind: DrumKind,
+    pub decay: f32,
+    pub tone: f32,
+    pub punch: f32,
+}
+
+// the 808 hat oscillators, relative to 400hz
+const HAT_RATIOS: [f32; 6] = [0.513, 0.761, 0.924, 1.307, 1.35, 2.0];
+
+pub struct DrumVoice {
+    age: u64,
+    phase: f32,
+    hat_phases: [f32; 6],
+    seed: u32,
+    hp: Biquad,
+    bp: Biquad,
+}
+
+impl DrumVoice {
+    pub fn new(f: f32, p: &DrumParams, seed: u32) -> DrumVoice {
+        let mut hp = Biquad::default();
+        let mut bp = Biquad::default();
+        match p.kind {
+            DrumKind::Kick | DrumKind::Tom => {
+                bp.set_bandpass(3000.0, 0.7);
+            },
+            DrumKind::Snare => {
+                hp.set_highpass(lerp(800.0, 3000.0, p.tone), 0.7);
+                bp.set_bandpass(5000.0, 0.5);
+            },
+            DrumKind::Hat => {
+                hp.set_highpass(lerp(5000.0, 9000.0, p.tone), 0.7);
+                bp.set_bandpass(10000.0, 0.8);
+            },
+            DrumKind::Clap => {
+                bp.set_bandpass(f.max(200.0), lerp(1.0, 4.0, p.tone));
+            },
+        }
+        DrumVoice {
+            age: 0,
+            phase: 0.0,
+            hat_phases: [0.0; 6],
+            seed,
+            hp,
+            bp,
+        }
+    }
+
+    fn noise(&mut self) -> f32 {
+        self.seed = khash(self.seed.wrapping_add(1));
+        self.seed as f32 / 4294967295.0 * 2.0 - 1.0
+    }
+
+    // swept sine, the pitch starts punch * 8 times higher and falls back in 30ms
+    fn body(&mut self, f: f32, punch: f32, t: f32) -> f32 {
+        let f = f * (1.0 + punch * 8.0 * (-t / 0.03).exp());
+        self.phase = (self.phase + f / SAMPLE_RATE).fract();
+        (2.0 * PI * self.phase).sin()
+    }
+
+    pub fn finished(&self, p: &DrumParams) -> bool {
+        self.age as f32 / SAMPLE_RATE > p.decay * 7.0 + 0.05
+    }
+
+    pub fn tick(&mut self, f: f32, p: &DrumParams) -> f32 {
+        let t = self.age as f32 / SAMPLE_RATE;
+        self.age += 1;
+        let decay = p.decay.max(0.005);
+        let env = (-t / decay).exp();
+
+        match p.kind {
+            DrumKind::Kick | DrumKind::Tom => {
+                let body = self.body(f, p.punch, t);
+                // click on the front, tone says how much
+                let n = self.noise();
+                let click = self.bp.tick(n) * (-t / 0.003).exp() * p.tone;
+                body * env + click
+            },
+            DrumKind::Snare => {
+                let body = self.body(f, p.punch * 0.25, t) * (-t / (decay * 0.5)).exp();
+                let n = self.noise();
+                let n = self.bp.tick(self.hp.tick(n)) * 2.0;
+                lerp(body, n, p.tone) * env
+            },
+            DrumKind::Hat => {
+                // six detuned squares make the metal, noise fills it in
+                let mut metal = 0.0;
+                for (ph, r) in self.hat_phases.iter_mut().zip(HAT_RATIOS.iter()) {
+                    *ph = (*ph + f * r / SAMPLE_RATE).fract();
+                    metal += if *ph < 0.5 { 1.0 } else { -1.0 };
+                }
+                let n = self.noise();
+                let x = metal / 6.0 + n * 0.5;
+                self.bp.tick(self.hp.tick(x)) * 2.0 * env
+            },
+            DrumKind::Clap => {
+                // three quick hits 10ms apart and then the tail
+                let hit = if t < 0.03 { (-(t % 0.01) / 0.003).exp() } else { 0.0 };
+                let tail = if t >= 0.02 { env } else { 0.0 };
+                let n = self.noise();
+                self.bp.tick(n) * 2.0 * hit.max(tail)
+            },
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::sync::Arc;
+
+use crate::dsp::*;
+use crate::kmath::*;
+use crate::voices::sampler::*;
+
+// Granular playback off the sample bank. Grains are spawned on an exact sample countdown
+// in tick, so density and spray dont depend on the block size.
+
+pub const MAX_GRAINS: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GrainWindow {
+    Hann,
+    Triangle,
+    Tukey,
+    Rect,
+}
+
+impl GrainWindow {
+    pub const ALL: [GrainWindow; 4] = [GrainWindow::Hann, GrainWindow::Triangle, GrainWindow::Tukey, GrainWindow::Rect];
+
+    pub fn from_param(x: f32) -> GrainWindow {
+        let i = (x.round().max(0.0) as usize).min(GrainWindow::ALL.len() - 1);
+        GrainWindow::ALL[i]
+    }
+
+    // t 0..1 through the grain
+    pub fn apply(&self, t: f32) -> f32 {
+        match self {
+            GrainWindow::Hann => 0.5 - 0.5 * (2.0 * PI * t).cos(),
+            GrainWindow::Triangle => 1.0 - (2.0 * t - 1.0).abs(),
+            // flat in the middle, quarter of it fading each end
+            GrainWindow::Tukey => {
+                let e = (t.min(1.0 - t) / 0.25).min(1.0);
+                0.5 - 0.5 * (PI * e).cos()
+            },
+            GrainWindow::Rect => 1.0,
+        }
+    }
+}
+
+// what the voice needs out of the SoundDesc
+#[derive(Clone, Copy)]
+pub struct GrainParams {
+    pub root: f32,
+    pub size_ms: f32,
+    pub density: f32,       // grains per second
+    pub pos: f32,           // 0..1 through the sample
+    pub pos_jitter: f32,    // fraction of the sample
+    pub pitch_jitter: f32,  // semitones
+    pub spray: f32,         // 0..1 randomness of the spacing
+    pub window: GrainWindow,
+    pub scan: f32,          // how fast pos moves, 1 is the samples own speed
+}
+
+#[derive(Clone, Copy, Default)]
+struct Grain {
+    active: bool,
+    pos: f64,
+    step: f64,
+    age: usize,
+    len: usize,
+}
+
+pub struct GranularVoice {
+    sample: Option<Arc<Sample>>,
+    grains: [Grain; MAX_GRAINS],
+    countdown: f32,
+    scan_pos: f64,
+    seed: u32,
+}
+
+impl GranularVoice {
+    pub fn new(sample: Option<Arc<Sample>>, seed: u32) -> GranularVoice {
+        GranularVoice {
+            sample,
+            grains: [Grain::default(); MAX_GRAINS],
+            countdown: 0.0,
+            scan_pos: 0.0,
+            seed,
+        }
+    }
+
+    // -1..1
+    fn rand(&mut self) -> f32 {
+        self.seed = khash(self.seed.wrapping_add(1));
+        self.seed as f32 / 4294967295.0 * 2.0 - 1.0
+    }
+
+    fn spawn(&mut self, f: f32, p: &GrainParams, len_samples: f64, rate: f32) {
+        let slot = match self.grains.iter().position(|g| !g.active) {
+            Some(i) => i,
+            None => return,
+        };
+        let pos = (p.pos as f64 * len_samples + self.scan_pos + (self.rand() * p.pos_jitter) as f64 * len_samples).rem_euclid(len_samples);
+        let pitch = 2.0f32.powf(self.rand() * p.pitch_jitter / 12.0);
+        let step = (f / p.root.max(1.0) * pitch * rate / SAMPLE_RATE) as f64;
+        self.grains[slot] = Grain {
+            active: true,
+            pos,
+            step,
+            age: 0,
+            len: ms_to_samples(p.size_ms).max(1.0) as usize,
+        };
+    }
+
+    pub fn tick(&mut self, f: f32, p: &GrainParams) -> f32 {
+        let (len, rate) = match &self.sample {
+            Some(s) if s.data.len() > 4 => (s.data.len() as f64, s.rate),
+            _ => return 0.0,
+        };
+
+        self.scan_pos = (self.scan_pos + (p.scan * rate / SAMPLE_RATE) as f64).rem_euclid(len);
+
+        self.countdown -= 1.0;
+        if self.countdown <= 0.0 {
+            self.spawn(f, p, len, rate);
+            let interval = SAMPLE_RATE / p.density.max(0.1);
+            self.countdown += (interval * (1.0 + p.spray * self.rand())).max(1.0);
+        }
+
+        let sample = self.sample.as_ref().unwrap();
+        let mut acc = 0.0;
+        for g in self.grains.iter_mut().filter(|g| g.active) {
+            let w = p.window.apply(g.age as f32 / g.len as f32);
+            acc += sample.read(g.pos.rem_euclid(len)) * w;
+            g.pos += g.step;
+            g.age += 1;
+            if g.age >= g.len {
+                g.active = false;
+            }
+        }
+
+        // overlapping grains add up, keep it roughly level as density goes up
+        let overlap = (p.density * p.size_ms * 0.001).max(1.0);
+        acc / overlap.sqrt()
+    }
+}
@@ -0,0 +1,78 @@
+pub mod string;
+pub mod osc;
+pub mod morph;
+pub mod sampler;
+pub mod granular;
+pub mod drums;
+pub mod sfx;
+pub mod click;
+
+use crate::voices::string::*;
+use crate::voices::osc::*;
+use crate::voices::morph::*;
+use crate::voices::sampler::*;
+use crate::voices::granular::*;
+use crate::voices::drums::*;
+use crate::voices::sfx::*;
+
+// What a Channel uses to make its sound. The envelope, compression and shaping
+// after it are the same whichever one it is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoiceKind {
+    Additive,
+    String,
+    Morph,
+    Sampler,
+    Granular,
+    Drum,
+    Sfx,
+}
+
+impl VoiceKind {
+    pub const ALL: [VoiceKind; 7] = [
+        VoiceKind::Additive,
+        VoiceKind::String,
+        VoiceKind::Morph,
+        VoiceKind::Sampler,
+        VoiceKind::Granular,
+        VoiceKind::Drum,
+        VoiceKind::Sfx,
+    ];
+
+    // its a knob so round it
+    pub fn from_param(x: f32) -> VoiceKind {
+        let i = (x.round().max(0.0) as usize).min(VoiceKind::ALL.len() - 1);
+        VoiceKind::ALL[i]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            VoiceKind::Additive => "oscillator",
+            VoiceKind::String => "string",
+            VoiceKind::Morph => "morph",
+            VoiceKind::Sampler => "sampler",
+            VoiceKind::Granular => "granular",
+            VoiceKind::Drum => "drum",
+            VoiceKind::Sfx => "sfx",
+        }
+    }
+}
+
+// the per voice state for whichever kind it is, additive keeps its phases on the Channel.
+// morph and granular are big so theyre boxed, the boxes come from the slots pool
+pub enum Generator {
+    Additive(AnalogState),
+    String(KarplusString),
+    Morph(Box<MorphOsc>),
+    Sampler(SamplerVoice),
+    Granular(Box<GranularVoice>),
+    Drum(DrumVoice),
+    Sfx(SfxSynth),
+}
+
+impl Generator {
+    // drums and sfx do their own envelopes, morph has its keyframes
+    pub fn own_envelope(&self) -> bool {
+        matches!(self, Generator::Drum(_) | Generator::Sfx(_) | Generator::Morph(_))
+    }
+}
@@ -0,0 +1,225 @@
+use crate::dsp::*;
+use crate::kmath::*;
+
+// Timbre that changes over the life of the note. A handful of keyframes, each a TimbreFrame
+// with a time and a curve, get interpolated every sample while the key is held. On release
+// it glides from wherever it was to the release frame.
+
+pub const MAX_KEYFRAMES: usize = 8;
+// detune voices, voices is fractional so the last one fades in
+pub const MORPH_VOICES: usize = 4;
+
+// sub, root, 3 mids, 8 highs
+const PARTIALS: [f32; 13] = [0.5, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0];
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TimbreFrame {
+    pub f: f32,         // semitones from the note
+    pub sub: f32,
+    pub root: f32,
+    pub mid: f32,
+    pub high: f32,
+    pub amp: f32,       // db
+    pub detune: f32,    // cents between voices
+    pub voices: f32,
+}
+
+// name, min, max for each field in order, the timeline uses these for its y axis
+pub static FRAME_FIELDS: [(&str, f32, f32); 8] = [
+    ("pitch", -24.0, 24.0),
+    ("sub", 0.0, 1.0),
+    ("root", 0.0, 1.0),
+    ("mid", 0.0, 1.0),
+    ("high", 0.0, 1.0),
+    ("amp", -40.0, 6.0),
+    ("detune", 0.0, 50.0),
+    ("voices", 1.0, MORPH_VOICES as f32),
+];
+
+impl TimbreFrame {
+    pub fn get(&self, field: usize) -> f32 {
+        match field {
+            0 => self.f,
+            1 => self.sub,
+            2 => self.root,
+            3 => self.mid,
+            4 => self.high,
+            5 => self.amp,
+            6 => self.detune,
+            _ => self.voices,
+        }
+    }
+
+    pub fn set(&mut self, field: usize, val: f32) {
+        let (_, min, max) = FRAME_FIELDS[field.min(FRAME_FIELDS.len() - 1)];
+        let val = val.clamp(min, max);
+        match field {
+            0 => self.f = val,
+            1 => self.sub = val,
+            2 => self.root = val,
+            3 => self.mid = val,
+            4 => self.high = val,
+            5 => self.amp = val,
+            6 => self.detune = val,
+            _ => self.voices = val,
+        }
+    }
+
+    pub fn lerp(a: &TimbreFrame, b: &TimbreFrame, t: f32) -> TimbreFrame {
+        let mut out = *a;
+        for i in 0..FRAME_FIELDS.len() {
+            out.set(i, lerp(a.get(i), b.get(i), t));
+        }
+        out
+    }
+
+    // amplitude of partial p
+    fn partial_amp(&self, p: usize) -> f32 {
+        let h = PARTIALS[p];
+        match p {
+            0 => self.sub,
+            1 => self.root,
+            2..=4 => self.mid * 2.0 / h,
+            _ => self.high * 5.0 / h,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Keyframe {
+    pub time: f32,      // seconds from note on, or from release for the release frame
+    pub curve: f32,     // shape of the segment coming into this frame, 1 is linear
+    pub frame: TimbreFrame,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MorphDesc {
+    pub keys: [Keyframe; MAX_KEYFRAMES],
+    pub len: usize,
+    pub release: Keyframe,
+}
+
+impl Default for MorphDesc {
+    fn default() -> Self {
+        let bright = TimbreFrame { f: 0.0, sub: 0.0, root: 1.0, mid: 0.6, high: 0.4, amp: 0.0, detune: 0.0, voices: 1.0 };
+        let dark = TimbreFrame { sub: 0.4, mid: 0.2, high: 0.0, detune: 12.0, voices: 3.0, ..bright };
+        let gone = TimbreFrame { mid: 0.0, high: 0.0, amp: -40.0, ..dark };
+        let mut keys = [Keyframe::default(); MAX_KEYFRAMES];
+        keys[0] = Keyframe { time: 0.0, curve: 1.0, frame: bright };
+        keys[1] = Keyframe { time: 1.5, curve: 0.5, frame: dark };
+        MorphDesc {
+            keys,
+            len: 2,
+            release: Keyframe { time: 0.5, curve: 1.0, frame: gone },
+        }
+    }
+}
+
+impl MorphDesc {
+    pub fn keys(&self) -> &[Keyframe] {
+        &self.keys[..self.len]
+    }
+
+    // while held, t in seconds since note on
+    pub fn frame_at(&self, t: f32) -> TimbreFrame {
+        let keys = self.keys();
+        if t <= keys[0].time {
+            return keys[0].frame;
+        }
+        for w in keys.windows(2) {
+            if t < w[1].time {
+                let u = (t - w[0].time) / (w[1].time - w[0].time).max(1e-6);
+                return TimbreFrame::lerp(&w[0].frame, &w[1].frame, u.powf(w[1].curve));
+            }
+        }
+        keys[keys.len() - 1].frame
+    }
+
+    pub fn frame_released(&self, t: f32, released_at: f32) -> TimbreFrame {
+        let from = self.frame_at(released_at);
+        let u = ((t - released_at) / self.release.time.max(1e-6)).min(1.0);
+        TimbreFrame::lerp(&from, &self.release.frame, u.powf(self.release.curve))
+    }
+
+    // keeps them sorted by time, gives back where it went
+    pub fn insert(&mut self, key: Keyframe) -> Option<usize> {
+        if self.len >= MAX_KEYFRAMES {
+            return None;
+        }
+        let idx = self.keys().iter().position(|k| k.time > key.time).unwrap_or(self.len);
+        for i in (idx..self.len).rev() {
+            self.keys[i + 1] = self.keys[i];
+        }
+        self.keys[idx] = key;
+        self.len += 1;
+        Some(idx)
+    }
+
+    // always leaves one
+    pub fn remove(&mut self, idx: usize) {
+        if self.len <= 1 || idx >= self.len {
+            return;
+        }
+        for i in idx..self.len - 1 {
+            self.keys[i] = self.keys[i + 1];
+        }
+        self.len -= 1;
+    }
+
+    // moves a key in time without letting it pass its neighbours
+    pub fn set_time(&mut self, idx: usize, time: f32) {
+        let lo = if idx == 0 { 0.0 } else { self.keys[idx - 1].time };
+        let hi = if idx + 1 < self.len { self.keys[idx + 1].time } else { f32::INFINITY };
+        self.keys[idx].time = time.clamp(lo, hi);
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct MorphOsc {
+    phases: [f32; MORPH_VOICES * PARTIALS.len()],
+}
+
+impl MorphOsc {
+    pub fn new(seed: u32) -> MorphOsc {
+        let mut phases = [0.0; MORPH_VOICES * PARTIALS.len()];
+        for (i, p) in phases.iter_mut().enumerate() {
+            *p = krand(seed.wrapping_add(i as u32));
+        }
+        MorphOsc { phases }
+    }
+
+    // done once the release frame has been reached
+    pub fn finished(&self, desc: &MorphDesc, age: u64, released: Option<u64>) -> bool {
+        released.is_some_and(|r| (age - r) as f32 / SAMPLE_RATE >= desc.release.time)
+    }
+
+    // age and release in samples since note on
+    pub fn tick(&mut self, f: f32, desc: &MorphDesc, age: u64, released: Option<u64>) -> f32 {
+        let t = age as f32 / SAMPLE_RATE;
+        let frame = match released {
+            Some(r) => desc.frame_released(t, r as f32 / SAMPLE_RATE),
+            None => desc.frame_at(t),
+        };
+
+        let base = f * 2.0f32.powf(frame.f / 12.0);
+        let voices = frame.voices.clamp(1.0, MORPH_VOICES as f32);
+        let n_voices = voices.ceil() as usize;
+        let mut acc = 0.0;
+        for v in 0..n_voices {
+            let g_voice = (voices - v as f32).min(1.0);
+            // spread the detune either side of the note
+            let spread = v as f32 - (voices - 1.0) / 2.0;
+            let ratio = 2.0f32.powf(frame.detune * spread / 1200.0);
+            for (p, h) in PARTIALS.iter().enumerate() {
+                let fp = base * h * ratio;
+                if fp > SAMPLE_RATE / 2.0 {
+                    continue;
+                }
+                let idx = v * PARTIALS.len() + p;
+                self.phases[idx] = (self.phases[idx] + fp / SAMPLE_RATE).fract();
+                acc += g_voice * frame.partial_amp(p) * (2.0 * PI * self.phases[idx]).sin();
+            }
+        }
+        acc / voices * 10.0f32.powf(0.05 * frame.amp)
+    }
+}
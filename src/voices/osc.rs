@@ -0,0 +1,70 @@
+use crate::dsp::*;
+use crate::kmath::*;
+
+// Band limited basic waveforms for the second oscillator and the sub.
+// Saw and square get polyBLEP corrections at the jumps, triangle and sine dont need them.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wave {
+    Sine,
+    Saw,
+    Square,
+    Triangle,
+}
+
+impl Wave {
+    pub const ALL: [Wave; 4] = [Wave::Sine, Wave::Saw, Wave::Square, Wave::Triangle];
+
+    pub fn from_param(x: f32) -> Wave {
+        let i = (x.round().max(0.0) as usize).min(Wave::ALL.len() - 1);
+        Wave::ALL[i]
+    }
+}
+
+// t is phase 0..1, dt is phase increment per sample
+fn poly_blep(t: f32, dt: f32) -> f32 {
+    if t < dt {
+        let t = t / dt;
+        t + t - t * t - 1.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt;
+        t * t + t + t + 1.0
+    } else {
+        0.0
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct BlepOsc {
+    pub phase: f32,
+}
+
+impl BlepOsc {
+    // also says if the phase wrapped this sample, for syncing off it
+    pub fn tick(&mut self, f: f32, wave: Wave) -> (f32, bool) {
+        let dt = (f / SAMPLE_RATE).min(0.5);
+        let t = self.phase;
+        let y = match wave {
+            Wave::Sine => (2.0 * PI * t).sin(),
+            Wave::Saw => 2.0 * t - 1.0 - poly_blep(t, dt),
+            Wave::Square => {
+                let naive = if t < 0.5 { 1.0 } else { -1.0 };
+                naive + poly_blep(t, dt) - poly_blep((t + 0.5).fract(), dt)
+            },
+            Wave::Triangle => 4.0 * (t - 0.5).abs() - 1.0,
+        };
+        self.phase += dt;
+        let wrapped = self.phase >= 1.0;
+        if wrapped {
+            self.phase -= 1.0;
+        }
+        (y, wrapped)
+    }
+}
+
+// the second oscillator and the sub riding along with the additive one
+#[derive(Clone, Copy, Default)]
+pub struct AnalogState {
+    pub osc2: BlepOsc,
+    pub sub: BlepOsc,
+}
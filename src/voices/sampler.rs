@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use crate::dsp::*;
+use crate::kmath::*;
+use crate::wav::*;
+
+// Sample playback. The GUI loads the file and sends the Arc over, voices just hold
+// a reference so nothing gets copied or freed on the audio thread while they play.
+
+pub const NUM_SAMPLES: usize = 16;
+
+pub struct Sample {
+    pub name: String,
+    pub rate: f32,
+    pub data: Vec<f32>,
+}
+
+impl std::fmt::Debug for Sample {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} ({} samples)", self.name, self.data.len())
+    }
+}
+
+impl Sample {
+    pub fn from_wav(name: &str, wav: WavData) -> Sample {
+        Sample {
+            name: name.to_string(),
+            rate: wav.rate,
+            data: wav.samples,
+        }
+    }
+
+    // 4 point hermite, clamped at the ends
+    pub fn read(&self, pos: f64) -> f32 {
+        let n = self.data.len() as i64;
+        let i = pos.floor() as i64;
+        let t = (pos - i as f64) as f32;
+        let at = |k: i64| self.data[(i + k).clamp(0, n - 1) as usize];
+        let (xm1, x0, x1, x2) = (at(-1), at(0), at(1), at(2));
+
+        let c1 = 0.5 * (x1 - xm1);
+        let c2 = xm1 - 2.5 * x0 + 2.0 * x1 - 0.5 * x2;
+        let c3 = 0.5 * (x2 - xm1) + 1.5 * (x0 - x1);
+        ((c3 * t + c2) * t + c1) * t + x0
+    }
+}
+
+// what the voice needs out of the SoundDesc, start and end are fractions of the sample.
+// one shots play start to end too, looping or not
+#[derive(Clone, Copy)]
+pub struct SamplerParams {
+    pub root: f32,
+    pub start: f32,
+    pub end: f32,
+    pub looping: bool,
+    pub xfade: f32,
+}
+
+impl SamplerParams {
+    // in samples
+    fn bounds(&self, len: f64) -> (f64, f64) {
+        let start = (self.start.clamp(0.0, 1.0) as f64 * len).floor();
+        let end = (self.end.clamp(0.0, 1.0) as f64 * len).floor().min(len - 1.0);
+        (start, end)
+    }
+}
+
+pub struct SamplerVoice {
+    sample: Option<Arc<Sample>>,
+    pos: f64,
+}
+
+impl SamplerVoice {
+    pub fn new(sample: Option<Arc<Sample>>, p: &SamplerParams) -> SamplerVoice {
+        let pos = sample.as_ref().map(|s| p.bounds(s.data.len() as f64).0).unwrap_or(0.0);
+        SamplerVoice { sample, pos }
+    }
+
+    pub fn tick(&mut self, f: f32, p: &SamplerParams) -> f32 {
+        let sample = match &self.sample {
+            Some(s) if s.data.len() > 4 => s,
+            _ => return 0.0,
+        };
+        let step = (f / p.root.max(1.0)) as f64 * (sample.rate / SAMPLE_RATE) as f64;
+
+        let (start, end) = p.bounds(sample.data.len() as f64);
+        let looping = p.looping && end - start > 4.0;
+
+        // the end of the loop fades into whats just before the loop start so the wrap doesnt click.
+        // if the loop starts too near the beginning for that, the loop start moves in by the
+        // fade and the bit it skips is what gets faded from, first time through still plays it
+        let xf_len = if looping { (p.xfade.clamp(0.0, 0.5) as f64 * (end - start)).floor() } else { 0.0 };
+        let loop_start = start.max(xf_len);
+        let loop_len = end - loop_start;
+
+        if looping {
+            while self.pos >= end {
+                self.pos -= loop_len;
+            }
+        } else if self.pos >= end {
+            return 0.0;
+        }
+
+        let mut y = sample.read(self.pos);
+
+        let xf_start = end - xf_len;
+        if xf_len > 1.0 && self.pos > xf_start {
+            let t = ((self.pos - xf_start) / xf_len) as f32;
+            y = lerp(y, sample.read(self.pos - loop_len), t);
+        }
+
+        self.pos += step;
+        y
+    }
+}
@@ -0,0 +1,472 @@
+use crate::effects::ParamDesc;
+use crate::kmath::*;
+
+// sfxr style game sound effects. Parameters are in sfxr's own 0..1 (or -1..1) units and the
+// synth runs the same way, 8x supersampled square/saw/sine/noise with slides, vibrato, arpeggio,
+// repeat and a resonant lowpass and highpass. It has its own envelope and ends itself.
+// The key doesnt change the pitch, any key fires it.
+
+pub const WAVE: usize = 0;
+pub const BASE_FREQ: usize = 1;
+pub const FREQ_LIMIT: usize = 2;
+pub const FREQ_RAMP: usize = 3;
+pub const FREQ_DRAMP: usize = 4;
+pub const DUTY: usize = 5;
+pub const DUTY_RAMP: usize = 6;
+pub const VIB_STRENGTH: usize = 7;
+pub const VIB_SPEED: usize = 8;
+pub const ENV_ATTACK: usize = 9;
+pub const ENV_SUSTAIN: usize = 10;
+pub const ENV_PUNCH: usize = 11;
+pub const ENV_DECAY: usize = 12;
+pub const ARP_MOD: usize = 13;
+pub const ARP_SPEED: usize = 14;
+pub const REPEAT_SPEED: usize = 15;
+pub const LPF_FREQ: usize = 16;
+pub const LPF_RAMP: usize = 17;
+pub const LPF_RESONANCE: usize = 18;
+pub const HPF_FREQ: usize = 19;
+pub const HPF_RAMP: usize = 20;
+
+pub const SFX_PARAMS: usize = 21;
+
+pub static SFX_PARAM_DESCS: [ParamDesc; SFX_PARAMS] = [
+    ParamDesc { name: "wave", min: 0.0, max: 3.0, default: 0.0 },
+    ParamDesc { name: "freq", min: 0.0, max: 1.0, default: 0.3 },
+    ParamDesc { name: "limit", min: 0.0, max: 1.0, default: 0.0 },
+    ParamDesc { name: "slide", min: -1.0, max: 1.0, default: 0.0 },
+    ParamDesc { name: "dslide", min: -1.0, max: 1.0, default: 0.0 },
+    ParamDesc { name: "duty", min: 0.0, max: 1.0, default: 0.0 },
+    ParamDesc { name: "duty sweep", min: -1.0, max: 1.0, default: 0.0 },
+    ParamDesc { name: "vib depth", min: 0.0, max: 1.0, default: 0.0 },
+    ParamDesc { name: "vib speed", min: 0.0, max: 1.0, default: 0.0 },
+    ParamDesc { name: "attack", min: 0.0, max: 1.0, default: 0.0 },
+    ParamDesc { name: "sustain", min: 0.0, max: 1.0, default: 0.3 },
+    ParamDesc { name: "punch", min: 0.0, max: 1.0, default: 0.0 },
+    ParamDesc { name: "decay", min: 0.0, max: 1.0, default: 0.4 },
+    ParamDesc { name: "arp mod", min: -1.0, max: 1.0, default: 0.0 },
+    ParamDesc { name: "arp speed", min: 0.0, max: 1.0, default: 0.0 },
+    ParamDesc { name: "repeat", min: 0.0, max: 1.0, default: 0.0 },
+    ParamDesc { name: "lpf", min: 0.0, max: 1.0, default: 1.0 },
+    ParamDesc { name: "lpf sweep", min: -1.0, max: 1.0, default: 0.0 },
+    ParamDesc { name: "lpf res", min: 0.0, max: 1.0, default: 0.0 },
+    ParamDesc { name: "hpf", min: 0.0, max: 1.0, default: 0.0 },
+    ParamDesc { name: "hpf sweep", min: -1.0, max: 1.0, default: 0.0 },
+];
+
+pub type SfxParams = [f32; SFX_PARAMS];
+
+pub fn default_params() -> SfxParams {
+    let mut p = [0.0; SFX_PARAMS];
+    for (i, d) in SFX_PARAM_DESCS.iter().enumerate() {
+        p[i] = d.default;
+    }
+    p
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SfxCategory {
+    Pickup,
+    Laser,
+    Explosion,
+    Powerup,
+    Hit,
+    Jump,
+    Blip,
+}
+
+impl SfxCategory {
+    pub const ALL: [SfxCategory; 7] = [
+        SfxCategory::Pickup,
+        SfxCategory::Laser,
+        SfxCategory::Explosion,
+        SfxCategory::Powerup,
+        SfxCategory::Hit,
+        SfxCategory::Jump,
+        SfxCategory::Blip,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SfxCategory::Pickup => "pickup",
+            SfxCategory::Laser => "laser",
+            SfxCategory::Explosion => "explosion",
+            SfxCategory::Powerup => "powerup",
+            SfxCategory::Hit => "hit",
+            SfxCategory::Jump => "jump",
+            SfxCategory::Blip => "blip",
+        }
+    }
+}
+
+// sfxrs rnd and frnd off a seed
+struct Rng(u32);
+
+impl Rng {
+    fn next(&mut self) -> f32 {
+        self.0 = khash(self.0.wrapping_add(0x9E3779B9));
+        self.0 as f32 / 4294967295.0
+    }
+
+    // 0..=n
+    fn rnd(&mut self, n: u32) -> u32 {
+        ((self.next() * (n + 1) as f32) as u32).min(n)
+    }
+
+    fn frnd(&mut self, range: f32) -> f32 {
+        self.next() * range
+    }
+
+    fn coin(&mut self) -> bool {
+        self.rnd(1) == 1
+    }
+}
+
+// a fresh patch for the category, locked params keep whatever they were in current
+pub fn generate(cat: SfxCategory, seed: u32, current: &SfxParams, locks: &[bool; SFX_PARAMS]) -> SfxParams {
+    let mut r = Rng(seed);
+    let mut p = default_params();
+    match cat {
+        SfxCategory::Pickup => {
+            p[BASE_FREQ] = 0.4 + r.frnd(0.5);
+            p[ENV_SUSTAIN] = r.frnd(0.1);
+            p[ENV_DECAY] = 0.1 + r.frnd(0.4);
+            p[ENV_PUNCH] = 0.3 + r.frnd(0.3);
+            if r.coin() {
+                p[ARP_SPEED] = 0.5 + r.frnd(0.2);
+                p[ARP_MOD] = 0.2 + r.frnd(0.4);
+            }
+        },
+        SfxCategory::Laser => {
+            p[WAVE] = r.rnd(2) as f32;
+            if p[WAVE] == 2.0 && r.coin() {
+                p[WAVE] = r.rnd(1) as f32;
+            }
+            p[BASE_FREQ] = 0.5 + r.frnd(0.5);
+            p[FREQ_LIMIT] = (p[BASE_FREQ] - 0.2 - r.frnd(0.6)).max(0.2);
+            p[FREQ_RAMP] = -0.15 - r.frnd(0.2);
+            if r.rnd(2) == 0 {
+                p[BASE_FREQ] = 0.3 + r.frnd(0.6);
+                p[FREQ_LIMIT] = r.frnd(0.1);
+                p[FREQ_RAMP] = -0.35 - r.frnd(0.3);
+            }
+            if r.coin() {
+                p[DUTY] = r.frnd(0.5);
+                p[DUTY_RAMP] = r.frnd(0.2);
+            } else {
+                p[DUTY] = 0.4 + r.frnd(0.5);
+                p[DUTY_RAMP] = -r.frnd(0.7);
+            }
+            p[ENV_SUSTAIN] = 0.1 + r.frnd(0.2);
+            p[ENV_DECAY] = r.frnd(0.4);
+            if r.coin() {
+                p[ENV_PUNCH] = r.frnd(0.3);
+            }
+            if r.coin() {
+                p[HPF_FREQ] = r.frnd(0.3);
+            }
+        },
+        SfxCategory::Explosion => {
+            p[WAVE] = 3.0;
+            if r.coin() {
+                p[BASE_FREQ] = 0.1 + r.frnd(0.4);
+                p[FREQ_RAMP] = -0.1 + r.frnd(0.4);
+            } else {
+                p[BASE_FREQ] = 0.2 + r.frnd(0.7);
+                p[FREQ_RAMP] = -0.2 - r.frnd(0.2);
+            }
+            p[BASE_FREQ] *= p[BASE_FREQ];
+            if r.rnd(4) == 0 {
+                p[FREQ_RAMP] = 0.0;
+            }
+            if r.rnd(2) == 0 {
+                p[REPEAT_SPEED] = 0.3 + r.frnd(0.5);
+            }
+            p[ENV_SUSTAIN] = 0.1 + r.frnd(0.3);
+            p[ENV_DECAY] = r.frnd(0.5);
+            p[ENV_PUNCH] = 0.2 + r.frnd(0.6);
+            if r.coin() {
+                p[VIB_STRENGTH] = r.frnd(0.7);
+                p[VIB_SPEED] = r.frnd(0.6);
+            }
+            if r.rnd(2) == 0 {
+                p[ARP_SPEED] = 0.6 + r.frnd(0.3);
+                p[ARP_MOD] = 0.8 - r.frnd(1.6);
+            }
+        },
+        SfxCategory::Powerup => {
+            if r.coin() {
+                p[WAVE] = 1.0;
+            } else {
+                p[DUTY] = r.frnd(0.6);
+            }
+            p[BASE_FREQ] = 0.2 + r.frnd(0.3);
+            if r.coin() {
+                p[FREQ_RAMP] = 0.1 + r.frnd(0.4);
+                p[REPEAT_SPEED] = 0.4 + r.frnd(0.4);
+            } else {
+                p[FREQ_RAMP] = 0.05 + r.frnd(0.2);
+                if r.coin() {
+                    p[VIB_STRENGTH] = r.frnd(0.7);
+                    p[VIB_SPEED] = r.frnd(0.6);
+                }
+            }
+            p[ENV_SUSTAIN] = r.frnd(0.4);
+            p[ENV_DECAY] = 0.1 + r.frnd(0.4);
+        },
+        SfxCategory::Hit => {
+            p[WAVE] = r.rnd(2) as f32;
+            if p[WAVE] == 2.0 {
+                p[WAVE] = 3.0;
+            }
+            if p[WAVE] == 0.0 {
+                p[DUTY] = r.frnd(0.6);
+            }
+            p[BASE_FREQ] = 0.2 + r.frnd(0.6);
+            p[FREQ_RAMP] = -0.3 - r.frnd(0.4);
+            p[ENV_SUSTAIN] = r.frnd(0.1);
+            p[ENV_DECAY] = 0.1 + r.frnd(0.2);
+            if r.coin() {
+                p[HPF_FREQ] = r.frnd(0.3);
+            }
+        },
+        SfxCategory::Jump => {
+            p[DUTY] = r.frnd(0.6);
+            p[BASE_FREQ] = 0.3 + r.frnd(0.3);
+            p[FREQ_RAMP] = 0.1 + r.frnd(0.2);
+            p[ENV_SUSTAIN] = 0.1 + r.frnd(0.3);
+            p[ENV_DECAY] = 0.1 + r.frnd(0.2);
+            if r.coin() {
+                p[HPF_FREQ] = r.frnd(0.3);
+            }
+            if r.coin() {
+                p[LPF_FREQ] = 1.0 - r.frnd(0.6);
+            }
+        },
+        SfxCategory::Blip => {
+            p[WAVE] = r.rnd(1) as f32;
+            if p[WAVE] == 0.0 {
+                p[DUTY] = r.frnd(0.6);
+            }
+            p[BASE_FREQ] = 0.2 + r.frnd(0.4);
+            p[ENV_SUSTAIN] = 0.1 + r.frnd(0.1);
+            p[ENV_DECAY] = r.frnd(0.2);
+            p[HPF_FREQ] = 0.1;
+        },
+    }
+    for i in 0..SFX_PARAMS {
+        if locks[i] {
+            p[i] = current[i];
+        }
+    }
+    p
+}
+
+// nudges about half the unlocked params a little, never the waveform
+pub fn mutate(current: &SfxParams, seed: u32, locks: &[bool; SFX_PARAMS]) -> SfxParams {
+    let mut r = Rng(seed);
+    let mut p = *current;
+    for i in 1..SFX_PARAMS {
+        if r.coin() && !locks[i] {
+            let d = &SFX_PARAM_DESCS[i];
+            p[i] = (p[i] + r.frnd(0.1) - 0.05).clamp(d.min, d.max);
+        }
+    }
+    p
+}
+
+pub struct SfxSynth {
+    phase: i32,
+    fperiod: f64,
+    fmaxperiod: f64,
+    fslide: f64,
+    fdslide: f64,
+    period: i32,
+    square_duty: f32,
+    square_slide: f32,
+    env_stage: usize,
+    env_time: i32,
+    env_length: [i32; 3],
+    env_vol: f32,
+    fltp: f32,
+    fltdp: f32,
+    fltw: f32,
+    fltw_d: f32,
+    fltdmp: f32,
+    fltphp: f32,
+    flthp: f32,
+    flthp_d: f32,
+    vib_phase: f32,
+    vib_speed: f32,
+    vib_amp: f32,
+    rep_time: i32,
+    rep_limit: i32,
+    arp_time: i32,
+    arp_limit: i32,
+    arp_mod: f64,
+    noise: [f32; 32],
+    seed: u32,
+    done: bool,
+}
+
+impl SfxSynth {
+    pub fn new(p: &SfxParams, seed: u32) -> SfxSynth {
+        let mut s = SfxSynth {
+            phase: 0,
+            fperiod: 0.0,
+            fmaxperiod: 0.0,
+            fslide: 0.0,
+            fdslide: 0.0,
+            period: 0,
+            square_duty: 0.0,
+            square_slide: 0.0,
+            env_stage: 0,
+            env_time: 0,
+            env_length: [
+                (p[ENV_ATTACK] * p[ENV_ATTACK] * 100000.0) as i32,
+                (p[ENV_SUSTAIN] * p[ENV_SUSTAIN] * 100000.0) as i32,
+                (p[ENV_DECAY] * p[ENV_DECAY] * 100000.0) as i32,
+            ],
+            env_vol: 0.0,
+            fltp: 0.0,
+            fltdp: 0.0,
+            fltw: p[LPF_FREQ].powi(3) * 0.1,
+            fltw_d: 1.0 + p[LPF_RAMP] * 0.0001,
+            fltdmp: 0.0,
+            fltphp: 0.0,
+            flthp: p[HPF_FREQ] * p[HPF_FREQ] * 0.1,
+            flthp_d: 1.0 + p[HPF_RAMP] * 0.0003,
+            vib_phase: 0.0,
+            vib_speed: p[VIB_SPEED] * p[VIB_SPEED] * 0.01,
+            vib_amp: p[VIB_STRENGTH] * 0.5,
+            rep_time: 0,
+            rep_limit: 0,
+            arp_time: 0,
+            arp_limit: 0,
+            arp_mod: 0.0,
+            noise: [0.0; 32],
+            seed,
+            done: false,
+        };
+        s.fltdmp = (5.0 / (1.0 + p[LPF_RESONANCE] * p[LPF_RESONANCE] * 20.0) * (0.01 + s.fltw)).min(0.8);
+        s.rep_limit = if p[REPEAT_SPEED] == 0.0 { 0 } else { ((1.0 - p[REPEAT_SPEED]).powi(2) * 20000.0 + 32.0) as i32 };
+        s.restart(p);
+        s.refill_noise();
+        s
+    }
+
+    // the frequency side, repeat comes back through here
+    fn restart(&mut self, p: &SfxParams) {
+        let bf = p[BASE_FREQ] as f64;
+        let fl = p[FREQ_LIMIT] as f64;
+        self.fperiod = 100.0 / (bf * bf + 0.001);
+        self.fmaxperiod = 100.0 / (fl * fl + 0.001);
+        self.fslide = 1.0 - (p[FREQ_RAMP] as f64).powi(3) * 0.01;
+        self.fdslide = -(p[FREQ_DRAMP] as f64).powi(3) * 0.000001;
+        self.square_duty = 0.5 - p[DUTY] * 0.5;
+        self.square_slide = -p[DUTY_RAMP] * 0.00005;
+        let am = p[ARP_MOD] as f64;
+        self.arp_mod = if am >= 0.0 { 1.0 - am * am * 0.9 } else { 1.0 + am * am * 10.0 };
+        self.arp_time = 0;
+        self.arp_limit = if p[ARP_SPEED] == 1.0 { 0 } else { ((1.0 - p[ARP_SPEED]).powi(2) * 20000.0 + 32.0) as i32 };
+    }
+
+    fn refill_noise(&mut self) {
+        for n in self.noise.iter_mut() {
+            self.seed = khash(self.seed.wrapping_add(1));
+            *n = self.seed as f32 / 4294967295.0 * 2.0 - 1.0;
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.done
+    }
+
+    pub fn tick(&mut self, p: &SfxParams) -> f32 {
+        if self.done {
+            return 0.0;
+        }
+
+        self.rep_time += 1;
+        if self.rep_limit != 0 && self.rep_time >= self.rep_limit {
+            self.rep_time = 0;
+            self.restart(p);
+        }
+
+        self.arp_time += 1;
+        if self.arp_limit != 0 && self.arp_time >= self.arp_limit {
+            self.arp_limit = 0;
+            self.fperiod *= self.arp_mod;
+        }
+
+        self.fslide += self.fdslide;
+        self.fperiod *= self.fslide;
+        if self.fperiod > self.fmaxperiod {
+            self.fperiod = self.fmaxperiod;
+            if p[FREQ_LIMIT] > 0.0 {
+                self.done = true;
+            }
+        }
+
+        let mut rfperiod = self.fperiod as f32;
+        if self.vib_amp > 0.0 {
+            self.vib_phase += self.vib_speed;
+            rfperiod = self.fperiod as f32 * (1.0 + self.vib_phase.sin() * self.vib_amp);
+        }
+        self.period = (rfperiod as i32).max(8);
+        self.square_duty = (self.square_duty + self.square_slide).clamp(0.0, 0.5);
+
+        self.env_time += 1;
+        while self.env_time > self.env_length[self.env_stage] {
+            self.env_time = 0;
+            self.env_stage += 1;
+            if self.env_stage == 3 {
+                self.done = true;
+                return 0.0;
+            }
+        }
+        let t = self.env_time as f32 / self.env_length[self.env_stage].max(1) as f32;
+        self.env_vol = match self.env_stage {
+            0 => t,
+            1 => 1.0 + (1.0 - t) * 2.0 * p[ENV_PUNCH],
+            _ => 1.0 - t,
+        };
+
+        self.fltw = (self.fltw * self.fltw_d).clamp(0.0, 0.1);
+        self.flthp = (self.flthp * self.flthp_d).clamp(0.00001, 0.1);
+
+        let wave = p[WAVE].round() as i32;
+        let mut ssample = 0.0;
+        for _ in 0..8 {
+            self.phase += 1;
+            if self.phase >= self.period {
+                self.phase %= self.period;
+                if wave == 3 {
+                    self.refill_noise();
+                }
+            }
+            let fp = self.phase as f32 / self.period as f32;
+            let sample = match wave {
+                0 => if fp < self.square_duty { 0.5 } else { -0.5 },
+                1 => 1.0 - fp * 2.0,
+                2 => (fp * 2.0 * PI).sin(),
+                _ => self.noise[(self.phase * 32 / self.period) as usize % 32],
+            };
+
+            let pp = self.fltp;
+            if p[LPF_FREQ] != 1.0 {
+                self.fltdp += (sample - self.fltp) * self.fltw;
+                self.fltdp -= self.fltdp * self.fltdmp;
+            } else {
+                self.fltp = sample;
+                self.fltdp = 0.0;
+            }
+            self.fltp += self.fltdp;
+            self.fltphp += self.fltp - pp;
+            self.fltphp -= self.fltphp * self.flthp;
+
+            ssample += self.fltphp * self.env_vol;
+        }
+        ssample / 8.0
+    }
+}
@@ -0,0 +1,98 @@
+use crate::dsp::*;
+use crate::kmath::*;
+
+// Karplus Strong string. A delay line one period long gets filled with an excitation
+// and fed back through an averaging lowpass (damping) and an allpass for the fractional
+// part of the period, so tuning doesnt snap to whole samples.
+
+// loop gain on top of the lowpass, stops very bright strings ringing forever
+const LOOP_GAIN: f32 = 0.998;
+
+// the line is always long enough for this, so a retune or slide can go anywhere above it
+const MIN_HZ: f32 = 20.0;
+
+// slots keep a pool of these so a note never allocates on the audio thread, pluck
+// restarts one at a new pitch
+#[derive(Clone)]
+pub struct KarplusString {
+    line: DelayLine,
+    f: f32,
+    len: usize,
+    damp: f32,
+    ap_coef: f32,
+    ap_x1: f32,
+    ap_y1: f32,
+    prev: f32,
+}
+
+impl Default for KarplusString {
+    fn default() -> Self {
+        KarplusString {
+            line: DelayLine::new((SAMPLE_RATE / MIN_HZ) as usize + 2),
+            f: MIN_HZ,
+            len: 2,
+            damp: 0.0,
+            ap_coef: 0.0,
+            ap_x1: 0.0,
+            ap_y1: 0.0,
+            prev: 0.0,
+        }
+    }
+}
+
+impl KarplusString {
+    // excite gives one period of excitation a sample at a time, damping 0 to 1, pick 0 to 1 along the string
+    pub fn pluck<E: FnMut(usize) -> f32>(&mut self, f: f32, damping: f32, pick: f32, mut excite: E) {
+        self.damp = 0.5 * damping.clamp(0.0, 1.0);
+        self.f = f;
+        (self.len, self.ap_coef) = KarplusString::period(f, self.damp);
+        self.ap_x1 = 0.0;
+        self.ap_y1 = 0.0;
+        self.prev = 0.0;
+
+        self.line.clear();
+        for i in 0..self.len {
+            self.line.push(excite(i));
+        }
+
+        // picking at a point cancels the harmonics with a node there, thats a comb on the excitation.
+        // done in place from the end, sample i is len - 1 - i back
+        let pick_delay = (pick.clamp(0.0, 1.0) * self.len as f32).round() as usize;
+        if pick_delay > 0 {
+            let back = |i: usize| self.len - 1 - i;
+            for i in (pick_delay..self.len).rev() {
+                let x = self.line.tap(back(i)) - self.line.tap(back(i - pick_delay));
+                self.line.set(back(i), x);
+            }
+        }
+    }
+
+    // the averaging filter delays by damp samples, keep the allpass between 0.1 and 1.1
+    fn period(f: f32, damp: f32) -> (usize, f32) {
+        let period = SAMPLE_RATE / f.max(MIN_HZ);
+        let len = ((period - damp - 0.1).floor() as usize).max(2);
+        let frac = period - damp - len as f32;
+        (len, (1.0 - frac) / (1.0 + frac))
+    }
+
+    // f is the channels current pitch, so retunes and slides move the string too
+    pub fn tick(&mut self, f: f32) -> f32 {
+        if f != self.f {
+            self.f = f;
+            (self.len, self.ap_coef) = KarplusString::period(f, self.damp);
+        }
+        let x = self.line.tap(self.len - 1);
+        let lp = lerp(x, self.prev, self.damp);
+        self.prev = x;
+        let y = self.ap_coef * lp + self.ap_x1 - self.ap_coef * self.ap_y1;
+        self.ap_x1 = lp;
+        self.ap_y1 = y;
+        self.line.push(y * LOOP_GAIN);
+        x
+    }
+}
+
+// white noise burst, seeded so a retrigger isnt identical
+pub fn noise_excitation(i: usize, seed: u32) -> f32 {
+    kuniform(seed.wrapping_add(i as u32), -1.0, 1.0)
+}
@@ -0,0 +1,85 @@
+use std::path::Path;
+
+// Just enough RIFF WAVE to get PCM and float files in. Everything comes out mono f32,
+// channels are averaged.
+
+pub struct WavData {
+    pub rate: f32,
+    pub samples: Vec<f32>,
+}
+
+fn u16_at(b: &[u8], i: usize) -> u16 {
+    u16::from_le_bytes([b[i], b[i + 1]])
+}
+
+fn u32_at(b: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes([b[i], b[i + 1], b[i + 2], b[i + 3]])
+}
+
+pub fn load_wav(path: &Path) -> Result<WavData, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    parse_wav(&bytes)
+}
+
+pub fn parse_wav(b: &[u8]) -> Result<WavData, String> {
+    if b.len() < 12 || &b[0..4] != b"RIFF" || &b[8..12] != b"WAVE" {
+        return Err("not a wav file".to_string());
+    }
+
+    // walk the chunks for fmt and data, skipping anything else
+    let mut fmt = None;
+    let mut data = None;
+    let mut i = 12;
+    while i + 8 <= b.len() {
+        let id = &b[i..i + 4];
+        let len = u32_at(b, i + 4) as usize;
+        let body = i + 8;
+        let end = (body + len).min(b.len());
+        if id == b"fmt " && len >= 16 {
+            // the header can claim more than the file has
+            if body + 16 > b.len() {
+                return Err("fmt chunk cut short".to_string());
+            }
+            fmt = Some(body);
+        } else if id == b"data" {
+            data = Some(&b[body..end]);
+        }
+        // chunks are padded to even lengths
+        i = body + len + (len & 1);
+    }
+
+    let fmt = fmt.ok_or("no fmt chunk")?;
+    let data = data.ok_or("no data chunk")?;
+
+    let mut format = u16_at(b, fmt);
+    let channels = u16_at(b, fmt + 2) as usize;
+    let rate = u32_at(b, fmt + 4) as f32;
+    let bits = u16_at(b, fmt + 14) as usize;
+    // extensible, the real format is the first two bytes of the subformat guid
+    if format == 0xFFFE && u32_at(b, fmt - 4) >= 40 {
+        if fmt + 26 > b.len() {
+            return Err("fmt chunk cut short".to_string());
+        }
+        format = u16_at(b, fmt + 24);
+    }
+    if channels == 0 {
+        return Err("no channels".to_string());
+    }
+
+    let width = bits / 8;
+    let read: fn(&[u8]) -> f32 = match (format, bits) {
+        (1, 8) => |s| (s[0] as f32 - 128.0) / 128.0,
+        (1, 16) => |s| i16::from_le_bytes([s[0], s[1]]) as f32 / 32768.0,
+        (1, 24) => |s| (i32::from_le_bytes([0, s[0], s[1], s[2]]) >> 8) as f32 / 8388608.0,
+        (1, 32) => |s| i32::from_le_bytes([s[0], s[1], s[2], s[3]]) as f32 / 2147483648.0,
+        (3, 32) => |s| f32::from_le_bytes([s[0], s[1], s[2], s[3]]),
+        _ => return Err(format!("unsupported wav format {} with {} bits", format, bits)),
+    };
+
+    let frame = width * channels;
+    let samples = data.chunks_exact(frame)
+        .map(|f| f.chunks_exact(width).map(read).sum::<f32>() / channels as f32)
+        .collect();
+
+    Ok(WavData { rate, samples })
+}
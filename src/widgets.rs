@@ -121,6 +121,7 @@ impl FloatSlider {
     }
 }
 
+#[derive(Clone)]
 pub struct Knob {
     pub t: f32, // 0..1
     pub sensitivity: f32,
@@ -195,6 +196,10 @@ impl Knob {
         lerp(self.min, self.max, self.t)
     }
 
+    pub fn set_val(&mut self, val: f32) {
+        self.t = ((val - self.min) / (self.max - self.min)).clamp(0.0, 1.0);
+    }
+
     pub fn new(default: f32, min: f32, max: f32, sensitivity: f32, label: &str) -> Knob {
         Knob {
             t: (default - min) / (max - min),
@@ -205,4 +210,36 @@ impl Knob {
             sensitivity,
         }
     }
-}
\ No newline at end of file
+}
+// returns true on the frame it's clicked
+pub fn button(inputs: &FrameInputState, outputs: &mut FrameOutputs, r: Rect, label: &str, lit: bool) -> bool {
+    let c_bg = if lit { v4(1.0, 1.0, 0.0, 1.0) } else { v4(0.3, 0.3, 0.3, 1.0) };
+    let c_text = if lit { v4(0.0, 0.0, 0.0, 1.0) } else { v4(1.0, 1.0, 1.0, 1.0) };
+    let hover = r.contains(inputs.mouse_pos);
+
+    outputs.canvas.put_rect(r, 1.1, if hover { c_bg + v4(0.1, 0.1, 0.1, 0.0) } else { c_bg });
+    let r_text = r.dilate_pc(-0.2);
+    let w = (r.w * 0.9 / label.len().max(1) as f32).min(r_text.h);
+    outputs.glyphs.push_center_str(label, r.x + r.w/2.0, r.y + (r.h - w)/2.0, w, w, 1.2, c_text);
+
+    hover && inputs.lmb == KeyStatus::JustPressed
+}
+
+// a knob thats only ever min or max, drawn as a button that flips it
+pub fn toggle(inputs: &FrameInputState, outputs: &mut FrameOutputs, r: Rect, knob: &mut Knob) -> bool {
+    let on = knob.t > 0.5;
+    if button(inputs, outputs, r, &knob.label, on) {
+        knob.t = if on { 0.0 } else { 1.0 };
+        return true;
+    }
+    false
+}
+
+// vertical level meter, level is linear and the scale runs -60 to 0 db
+pub fn meter(outputs: &mut FrameOutputs, r: Rect, level: f32) {
+    outputs.canvas.put_rect(r, 1.1, v4(0.1, 0.1, 0.1, 1.0));
+    let db = 20.0 * level.max(1e-6).log10();
+    let t = ((db + 60.0) / 60.0).clamp(0.0, 1.0);
+    let c = if db > -6.0 { v4(1.0, 0.2, 0.0, 1.0) } else { v4(0.2, 0.9, 0.2, 1.0) };
+    outputs.canvas.put_rect(r.child(0.0, 1.0 - t, 1.0, t), 1.2, c);
+}